@@ -26,4 +26,21 @@ fn main() {
             .write_to_file(out_path.join("sysctl.rs"))
             .expect("Couldn't write bindings!");
     }
+
+    #[cfg(feature = "capi")]
+    {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let config = cbindgen::Config::from_file(PathBuf::from(&crate_dir).join("cbindgen.toml"))
+            .expect("Unable to read cbindgen.toml");
+
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(PathBuf::from(&crate_dir).join("src/capi.rs"))
+            .generate()
+            .expect("Unable to generate C header")
+            .write_to_file(PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("tty_info.h"));
+    }
 }
@@ -0,0 +1,4 @@
+//! Static and dynamic library exporting the C ABI of `tty-info`, declared in
+//! `include/tty_info.h`.
+
+pub use tty_info::capi::*;
@@ -0,0 +1,36 @@
+//! Compiles `tests/tty_info.c` against the static library and runs it.
+
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn c_program() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = env::current_exe().unwrap();
+    // target/<profile>/deps/<test> -> target/<profile>
+    let profile = exe.parent().unwrap().parent().unwrap();
+    let lib = profile.join("libtty_info_capi.a");
+    assert!(lib.exists(), "{} not built", lib.display());
+
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("tty_info_c");
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(&cc)
+        .args(["-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(manifest.join("../include"))
+        .arg(manifest.join("tests/tty_info.c"))
+        .arg(&lib)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&program)
+        .status()
+        .unwrap_or_else(|err| panic!("failed to run {cc}: {err}"));
+    assert!(status.success(), "{cc} failed: {status}");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{} failed: {}\n{}",
+        program.display(),
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"ok\n");
+}
@@ -0,0 +1,89 @@
+/*
+ * Exercises the C ABI of the library.
+ *
+ *   cargo test -p tty-info-capi
+ */
+
+#define _GNU_SOURCE
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <unistd.h>
+
+#include "tty_info.h"
+
+static void test_pty(void) {
+    int master = posix_openpt(O_RDWR | O_NOCTTY);
+    if (master < 0 || grantpt(master) != 0 || unlockpt(master) != 0) {
+        fprintf(stderr, "skipping pty test: %s\n", strerror(errno));
+        return;
+    }
+
+    const char *slave = ptsname(master);
+    struct stat st;
+    assert(slave != NULL && stat(slave, &st) == 0);
+
+    struct tty_info *info = NULL;
+    struct tty_info_error err;
+    int rc = tty_info_by_device(major(st.st_rdev), minor(st.st_rdev), NULL, 0,
+                                NULL, 0, &info, &err);
+    assert(rc == 0 && info != NULL);
+    assert(strcmp(tty_info_path(info), slave) == 0);
+    assert(strcmp(tty_info_name(info), slave + strlen("/dev/")) == 0);
+    assert(tty_info_major(info) == major(st.st_rdev));
+    assert(tty_info_minor(info) == minor(st.st_rdev));
+    tty_info_free(info);
+
+    const char *dirs[] = {"/dev/pts"};
+    uint8_t buf[2048];
+    rc = tty_info_by_device(major(st.st_rdev), minor(st.st_rdev), dirs, 1, buf,
+                            sizeof(buf), &info, NULL);
+    assert(rc == 0 && info != NULL);
+    assert(strcmp(tty_info_path(info), slave) == 0);
+    assert(strncmp(tty_info_name(info), "pts/", 4) != 0);
+    tty_info_free(info);
+
+    close(master);
+}
+
+static void test_errors(void) {
+    struct tty_info *info = NULL;
+    struct tty_info_error err;
+
+    int rc = tty_info_by_device(1, 3, NULL, 0, NULL, 0, &info, &err);
+    assert(rc == -ENOTTY && info == NULL);
+    assert(err.operation == TTY_INFO_OP_LOOKUP && err.errnum == ENOTTY);
+
+    rc = tty_info_for_process(UINT32_MAX, NULL, 0, NULL, 0, &info, &err);
+    assert(rc == -ESRCH && info == NULL);
+    assert(strcmp(err.path, "/proc/4294967295/stat") == 0);
+
+    tty_info_free(NULL);
+}
+
+static void test_processes(void) {
+    struct tty_info *current = NULL, *self = NULL;
+
+    assert(tty_info_current(NULL, 0, NULL, 0, &current, NULL) == 0);
+    assert(tty_info_for_process(getpid(), NULL, 0, NULL, 0, &self, NULL) == 0);
+    assert((current == NULL) == (self == NULL));
+    if (current != NULL) {
+        assert(strcmp(tty_info_path(current), tty_info_path(self)) == 0);
+    }
+
+    tty_info_free(current);
+    tty_info_free(self);
+}
+
+int main(void) {
+    test_pty();
+    test_errors();
+    test_processes();
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,164 @@
+#ifndef TTY_INFO_H
+#define TTY_INFO_H
+
+/* Generated by cbindgen from src/capi.rs, do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Opening a file or directory.
+#define TTY_INFO_OP_OPEN 1
+
+// Reading directory entries.
+#define TTY_INFO_OP_GETDENTS64 2
+
+// Seeking in a directory.
+#define TTY_INFO_OP_LSEEK 3
+
+// Reading file metadata.
+#define TTY_INFO_OP_FSTATAT 4
+
+// Reading a file.
+#define TTY_INFO_OP_READ 5
+
+// Parsing the content of a file.
+#define TTY_INFO_OP_PARSE 6
+
+// Growing a buffer.
+#define TTY_INFO_OP_ALLOC 7
+
+// Searching a device or process which was not found.
+#define TTY_INFO_OP_LOOKUP 8
+
+// Opening a process file descriptor.
+#define TTY_INFO_OP_PIDFD_OPEN 9
+
+// Signaling a process file descriptor.
+#define TTY_INFO_OP_PIDFD_SEND_SIGNAL 10
+
+// Reading the system clock.
+#define TTY_INFO_OP_CLOCK_GETTIME 11
+
+// Querying process informations from the kernel.
+#define TTY_INFO_OP_SYSCTL 12
+
+// Watching a directory for changes.
+#define TTY_INFO_OP_INOTIFY 13
+
+// Opening a socket.
+#define TTY_INFO_OP_SOCKET 14
+
+// Walking a directory tree.
+#define TTY_INFO_OP_WALK 15
+
+// Reading file metadata with `statx`.
+#define TTY_INFO_OP_STATX 16
+
+// A tty found by the library, to be released with `tty_info_free`.
+struct tty_info;
+
+// Details of a failed call.
+struct tty_info_error {
+  // The failed operation, one of the `TTY_INFO_OP_*` constants.
+  int operation;
+  // The resulting errno.
+  int errnum;
+  // The path the operation was working on, empty if none, keeping its end
+  // if it was too long.
+  char path[65];
+};
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Finds the tty with device number `major:minor` in the `ndirs` directories
+// of `dirs`, or in `/dev` if `dirs` is `NULL`.
+//
+// `dirent_buf` is used as buffer to read directories, a `NULL` one using a
+// 4096 bytes buffer on the stack. On success, stores the tty in `out`, to be
+// released with `tty_info_free`. Returns `-ENOTTY` if the device is not a
+// tty and `-ENOENT` if it was not found.
+//
+// # Safety
+//
+// `dirs` must be `NULL` or point to `ndirs` C strings, `dirent_buf` must be
+// `NULL` or point to `dirent_buf_len` writable bytes, `out` must be valid
+// for writes and `err` must be `NULL` or valid for writes.
+int tty_info_by_device(uint32_t major,
+                       uint32_t minor,
+                       const char *const *dirs,
+                       size_t ndirs,
+                       uint8_t *dirent_buf,
+                       size_t dirent_buf_len,
+                       struct tty_info **out,
+                       struct tty_info_error *err);
+
+// Same as `tty_info_by_device` for the controlling terminal of the `pid`
+// process, storing `NULL` in `out` if it has none.
+//
+// # Safety
+//
+// Same as [tty_info_by_device].
+int tty_info_for_process(uint32_t pid,
+                         const char *const *dirs,
+                         size_t ndirs,
+                         uint8_t *dirent_buf,
+                         size_t dirent_buf_len,
+                         struct tty_info **out,
+                         struct tty_info_error *err);
+
+// Same as `tty_info_for_process` for the current process.
+//
+// # Safety
+//
+// Same as [tty_info_by_device].
+int tty_info_current(const char *const *dirs,
+                     size_t ndirs,
+                     uint8_t *dirent_buf,
+                     size_t dirent_buf_len,
+                     struct tty_info **out,
+                     struct tty_info_error *err);
+
+// Returns the full path of `info`, valid until it is freed.
+//
+// # Safety
+//
+// `info` must have been returned by this library and not freed.
+const char *tty_info_path(const struct tty_info *info);
+
+// Returns the name of `info`, its path relative to the directory it was
+// found in, valid until it is freed.
+//
+// # Safety
+//
+// Same as [tty_info_path].
+const char *tty_info_name(const struct tty_info *info);
+
+// Returns the major device number of `info`.
+//
+// # Safety
+//
+// Same as [tty_info_path].
+uint32_t tty_info_major(const struct tty_info *info);
+
+// Returns the minor device number of `info`.
+//
+// # Safety
+//
+// Same as [tty_info_path].
+uint32_t tty_info_minor(const struct tty_info *info);
+
+// Releases `info`, doing nothing if it is `NULL`.
+//
+// # Safety
+//
+// `info` must be `NULL` or have been returned by this library and not
+// freed.
+void tty_info_free(struct tty_info *info);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TTY_INFO_H */
@@ -0,0 +1,507 @@
+//! Prints informations about terminals.
+//!
+//! Without arguments, prints the path of the terminal on standard input and
+//! exits with the same status as `tty(1)`.
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod cli {
+    use std::{
+        collections::{btree_map, BTreeMap},
+        ffi::{CString, OsString},
+        fs::File,
+        io::{self, Write},
+        ops::ControlFlow,
+        os::{
+            fd::AsFd,
+            unix::{
+                ffi::{OsStrExt, OsStringExt},
+                fs::{FileTypeExt, MetadataExt},
+            },
+        },
+        process::ExitCode,
+    };
+
+    use serde::Serialize;
+    use tty_info::{
+        parse_pid, Dev, Dir, DirentBuf, Errno, Error, Operation, ProcessInfo, RawProcessInfo,
+        SearchOptions, TtyInfo, VecBuffer,
+    };
+
+    const USAGE: &str = "\
+Usage: tty-info [OPTIONS]
+       tty-info [OPTIONS] -p PID...
+       tty-info [OPTIONS] -d MAJOR:MINOR|PATH...
+       tty-info [OPTIONS] -l
+
+Prints the terminal on standard input, the controlling terminal of each PID,
+the terminal of each device or all terminals and their processes.
+
+Options:
+  -p, --pid          print the controlling terminal of each PID
+  -d, --device       print the terminal of each device number or path
+  -l, --list         list all terminals and their processes
+  -D, --dir DIR      look for terminals in DIR instead of /dev, can be repeated
+      --json         print JSON
+  -s, --silent       print nothing, only return an exit status
+  -h, --help         print this help
+
+Exit status:
+  0  every terminal was found
+  1  standard input, a process or a device is not a terminal
+  2  invalid arguments
+  3  an error occurred
+";
+
+    const NOT_A_TTY: u8 = 1;
+    const USAGE_ERROR: u8 = 2;
+    const FAILURE: u8 = 3;
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Mode {
+        Stdin,
+        Pids,
+        Devices,
+        List,
+    }
+
+    struct Args {
+        mode: Mode,
+        dirs: Vec<CString>,
+        json: bool,
+        silent: bool,
+        operands: Vec<OsString>,
+    }
+
+    impl Args {
+        fn parse(args: impl Iterator<Item = OsString>) -> Result<Option<Self>, String> {
+            let mut mode = None;
+            let mut args = args.peekable();
+            let mut this = Self {
+                mode: Mode::Stdin,
+                dirs: Vec::new(),
+                json: false,
+                silent: false,
+                operands: Vec::new(),
+            };
+
+            let mut set_mode = |new| match mode.replace(new) {
+                Some(old) if old != new => Err("-p, -d and -l are exclusive".to_owned()),
+                _ => Ok(()),
+            };
+
+            while let Some(arg) = args.next() {
+                match arg.as_bytes() {
+                    b"-p" | b"--pid" => set_mode(Mode::Pids)?,
+                    b"-d" | b"--device" => set_mode(Mode::Devices)?,
+                    b"-l" | b"--list" => set_mode(Mode::List)?,
+                    b"-D" | b"--dir" => {
+                        let dir = args.next().ok_or("missing argument to --dir")?;
+                        this.dirs
+                            .push(CString::new(dir.into_vec()).map_err(|_| "invalid directory")?);
+                    }
+                    b"--json" => this.json = true,
+                    b"-s" | b"--silent" | b"--quiet" => this.silent = true,
+                    b"-h" | b"--help" => return Ok(None),
+                    b"--" => this.operands.extend(args.by_ref()),
+                    [b'-', _, ..] => {
+                        return Err(format!("unknown option {}", arg.to_string_lossy()))
+                    }
+                    _ => this.operands.push(arg),
+                }
+            }
+
+            this.mode = mode.unwrap_or(Mode::Stdin);
+            match this.mode {
+                Mode::Stdin | Mode::List if !this.operands.is_empty() => {
+                    Err("unexpected operand, use -p or -d".to_owned())
+                }
+                Mode::Pids | Mode::Devices if this.operands.is_empty() => {
+                    Err("missing operand".to_owned())
+                }
+                _ => Ok(Some(this)),
+            }
+        }
+
+        fn by_device(&self, dev: Dev) -> Result<TtyInfo, Error> {
+            if self.dirs.is_empty() {
+                TtyInfo::by_device(dev)
+            } else {
+                TtyInfo::by_device_in(dev, self.dirs.iter().map(CString::as_c_str))
+            }
+        }
+
+        /// Calls `visit` with each terminal of the searched directories.
+        fn enumerate(&self, visit: impl FnMut(TtyInfo) -> ControlFlow<()>) -> Result<(), Error> {
+            if self.dirs.is_empty() {
+                TtyInfo::enumerate(visit)?;
+            } else {
+                let dirs = self.dirs.iter().map(CString::as_c_str);
+                TtyInfo::enumerate_in(dirs, &SearchOptions::new(), visit)?;
+            }
+            Ok(())
+        }
+
+        fn for_process(&self, pid: u32) -> Result<ProcessInfo<impl DirentBuf>, Error> {
+            if self.dirs.is_empty() {
+                ProcessInfo::for_process(pid)
+            } else {
+                ProcessInfo::for_process_in(pid, self.dirs.iter().map(CString::as_c_str))
+            }
+        }
+    }
+
+    /// Accumulates the exit status and prints errors.
+    struct Status(u8);
+
+    impl Status {
+        fn error(&mut self, err: impl std::fmt::Display) {
+            eprintln!("tty-info: {err}");
+            self.0 = self.0.max(FAILURE);
+        }
+
+        fn not_a_tty(&mut self) {
+            self.0 = self.0.max(NOT_A_TTY);
+        }
+    }
+
+    /// Returns `true` if `err` means that a device is not a terminal.
+    fn is_not_a_tty(err: &Error) -> bool {
+        err.operation() == Operation::Lookup && matches!(err.errno(), Errno::ENOTTY | Errno::ENOENT)
+    }
+
+    /// Formats a device as `major:minor`.
+    fn fmt_dev(dev: Dev) -> String {
+        format!("{}:{}", dev.major(), dev.minor())
+    }
+
+    fn parse_dev(arg: &OsString) -> Result<Dev, String> {
+        let bytes = arg.as_bytes();
+        if !bytes.contains(&b'/') {
+            if let Some((major, minor)) = arg.to_str().and_then(|arg| arg.split_once(':')) {
+                return match (major.parse(), minor.parse()) {
+                    (Ok(major), Ok(minor)) => Ok(Dev::from_split(major, minor)),
+                    _ => Err(format!("invalid device number {major}:{minor}")),
+                };
+            }
+        }
+
+        let metadata =
+            std::fs::metadata(arg).map_err(|err| format!("{}: {err}", arg.to_string_lossy()))?;
+        if !metadata.file_type().is_char_device() {
+            return Err(format!("{}: not a character device", arg.to_string_lossy()));
+        }
+        Ok(Dev::from_u64(metadata.rdev()))
+    }
+
+    /// The terminal on standard input, like `tty(1)`.
+    fn stdin(args: &Args, status: &mut Status, out: &mut impl Write) -> io::Result<()> {
+        let metadata = io::stdin()
+            .as_fd()
+            .try_clone_to_owned()
+            .map(File::from)
+            .and_then(|file| file.metadata());
+
+        let tty = match metadata {
+            Ok(metadata) if metadata.file_type().is_char_device() => {
+                match args.by_device(Dev::from_u64(metadata.rdev())) {
+                    Ok(tty) => Some(tty),
+                    Err(err) if is_not_a_tty(&err) => None,
+                    Err(err) => {
+                        status.error(err);
+                        return Ok(());
+                    }
+                }
+            }
+            Ok(_) => None,
+            Err(err) => {
+                status.error(err);
+                return Ok(());
+            }
+        };
+
+        if tty.is_none() {
+            status.not_a_tty();
+        }
+        if args.silent {
+            return Ok(());
+        }
+
+        match (&tty, args.json) {
+            (tty, true) => print_json(out, tty),
+            (Some(tty), false) => writeln!(out, "{}", tty.path().to_string_lossy()),
+            (None, false) => writeln!(out, "not a tty"),
+        }
+    }
+
+    /// A process, with only its terminal device number if it has no node.
+    #[derive(Serialize)]
+    #[serde(untagged, bound = "")]
+    enum Process<B: DirentBuf> {
+        Info(Box<ProcessInfo<B>>),
+        Raw(RawProcessInfo),
+    }
+
+    fn pids(args: &Args, status: &mut Status, out: &mut impl Write) -> io::Result<()> {
+        let mut infos = Vec::new();
+
+        for arg in &args.operands {
+            let pid = match arg.to_str().and_then(|arg| arg.parse::<u32>().ok()) {
+                Some(pid) => pid,
+                None => {
+                    status.error(format!("invalid pid {}", arg.to_string_lossy()));
+                    continue;
+                }
+            };
+
+            let info = match args.for_process(pid) {
+                Ok(info) => Process::Info(Box::new(info)),
+                // The process has a terminal without device node, or none
+                // at all if it is `0:0`.
+                Err(err) if is_not_a_tty(&err) => match RawProcessInfo::for_process(pid) {
+                    Ok(info) => Process::Raw(info),
+                    Err(err) => {
+                        status.error(err);
+                        continue;
+                    }
+                },
+                Err(err) => {
+                    status.error(err);
+                    continue;
+                }
+            };
+            let tty = match &info {
+                Process::Info(info) => info.tty.as_ref(),
+                Process::Raw(_) => None,
+            };
+
+            if tty.is_none() {
+                status.not_a_tty();
+            }
+            if !args.json && !args.silent {
+                match (&tty, &info) {
+                    (Some(tty), _) => writeln!(out, "{pid}\t{}", tty.path().to_string_lossy())?,
+                    (None, Process::Raw(RawProcessInfo { tty: Some(dev), .. })) => {
+                        writeln!(out, "{pid}\t{}\tnot a tty", fmt_dev(*dev))?
+                    }
+                    (None, _) => writeln!(out, "{pid}\tnot a tty")?,
+                }
+            }
+            infos.push(info);
+        }
+
+        if args.json && !args.silent {
+            print_json(out, &infos)?;
+        }
+        Ok(())
+    }
+
+    fn devices(args: &Args, status: &mut Status, out: &mut impl Write) -> io::Result<()> {
+        let mut ttys = Vec::new();
+
+        for arg in &args.operands {
+            let dev = match parse_dev(arg) {
+                Ok(dev) => dev,
+                Err(err) => {
+                    status.error(err);
+                    continue;
+                }
+            };
+
+            match args.by_device(dev) {
+                Ok(tty) => {
+                    if !args.json && !args.silent {
+                        writeln!(out, "{}\t{}", fmt_dev(dev), tty.path().to_string_lossy())?;
+                    }
+                    ttys.push(tty);
+                }
+                Err(err) if is_not_a_tty(&err) => {
+                    status.not_a_tty();
+                    if !args.json && !args.silent {
+                        writeln!(out, "{}\tnot a tty", fmt_dev(dev))?;
+                    }
+                }
+                Err(err) => status.error(err),
+            }
+        }
+
+        if args.json && !args.silent {
+            print_json(out, &ttys)?;
+        }
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Entry {
+        #[serde(with = "tty_info::serde_dev")]
+        device: Dev,
+        tty: Option<TtyInfo>,
+        processes: Vec<RawProcessInfo>,
+    }
+
+    /// Lists the processes of `/proc` grouped by controlling terminal.
+    fn processes_by_tty() -> Result<BTreeMap<u64, Vec<RawProcessInfo>>, Error> {
+        let mut by_tty = BTreeMap::<u64, Vec<RawProcessInfo>>::new();
+        let mut dir = Dir::open(c"/proc")?;
+        let mut buf = VecBuffer::new();
+
+        let mut entries = dir.iter(&mut buf)?;
+        while let Some(entry) = entries.next() {
+            let pid = match parse_pid(entry?.name()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+
+            match RawProcessInfo::for_process(pid) {
+                Ok(info @ RawProcessInfo { tty: Some(tty), .. }) => {
+                    by_tty.entry(tty.as_u64()).or_default().push(info)
+                }
+                Ok(RawProcessInfo { tty: None, .. }) => (),
+                Err(err) if err.is_process_gone() => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(by_tty)
+    }
+
+    fn list(args: &Args, status: &mut Status, out: &mut impl Write) -> io::Result<()> {
+        let by_tty = match processes_by_tty() {
+            Ok(by_tty) => by_tty,
+            Err(err) => {
+                status.error(err);
+                return Ok(());
+            }
+        };
+
+        // Every terminal, with the first node found for it, then the
+        // terminals of processes that have no node.
+        let mut entries = BTreeMap::new();
+        let res = args.enumerate(|tty| {
+            if let btree_map::Entry::Vacant(entry) = entries.entry(tty.device().as_u64()) {
+                entry.insert(Entry {
+                    device: tty.device(),
+                    tty: Some(tty),
+                    processes: Vec::new(),
+                });
+            }
+            ControlFlow::Continue(())
+        });
+        if let Err(err) = res {
+            status.error(err);
+        }
+        for (dev, processes) in by_tty {
+            entries
+                .entry(dev)
+                .or_insert_with(|| Entry {
+                    device: Dev::from_u64(dev),
+                    tty: None,
+                    processes: Vec::new(),
+                })
+                .processes = processes;
+        }
+        let entries = entries.into_values().collect::<Vec<_>>();
+
+        if args.silent {
+            return Ok(());
+        }
+        if args.json {
+            return print_json(out, &entries);
+        }
+
+        for entry in &entries {
+            match &entry.tty {
+                Some(tty) => write!(out, "{}", tty.path().to_string_lossy())?,
+                None => write!(out, "{}", fmt_dev(entry.device))?,
+            }
+            for (i, process) in entry.processes.iter().enumerate() {
+                let sep = if i == 0 { '\t' } else { ',' };
+                write!(out, "{sep}{}", process.pid)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    fn print_json(out: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, value)?;
+        writeln!(out)
+    }
+
+    pub fn main() -> ExitCode {
+        let args = match Args::parse(std::env::args_os().skip(1)) {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                print!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            Err(err) => {
+                eprintln!("tty-info: {err}\n\n{USAGE}");
+                return ExitCode::from(USAGE_ERROR);
+            }
+        };
+
+        let mut status = Status(0);
+        let mut out = io::stdout().lock();
+        let res = match args.mode {
+            Mode::Stdin => stdin(&args, &mut status, &mut out),
+            Mode::Pids => pids(&args, &mut status, &mut out),
+            Mode::Devices => devices(&args, &mut status, &mut out),
+            Mode::List => list(&args, &mut status, &mut out),
+        };
+        if let Err(err) = res.and_then(|()| out.flush()) {
+            status.error(err);
+        }
+
+        ExitCode::from(status.0)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+            Args::parse(args.iter().map(OsString::from))
+        }
+
+        #[test]
+        fn parse_args() {
+            let args = parse(&["-s", "-D", "/dev/pts", "-p", "1", "--", "-2"])
+                .unwrap()
+                .unwrap();
+            assert!(args.mode == Mode::Pids && args.silent && !args.json);
+            assert_eq!(args.dirs, [c"/dev/pts"]);
+            assert_eq!(args.operands, ["1", "-2"]);
+
+            assert!(parse(&[]).unwrap().unwrap().mode == Mode::Stdin);
+            assert!(parse(&["--help"]).unwrap().is_none());
+            assert!(parse(&["-p", "1", "-d", "4:1"]).is_err());
+            assert!(parse(&["-l", "1"]).is_err());
+            assert!(parse(&["-d"]).is_err());
+            assert!(parse(&["-D"]).is_err());
+            assert!(parse(&["-x"]).is_err());
+        }
+
+        #[test]
+        fn parse_devices() {
+            let dev = parse_dev(&OsString::from("136:3")).unwrap();
+            assert_eq!((dev.major(), dev.minor()), (136, 3));
+            assert_eq!(
+                parse_dev(&OsString::from("/dev/null")).unwrap(),
+                Dev::from_split(1, 3)
+            );
+            assert!(parse_dev(&OsString::from("136:")).is_err());
+            assert!(parse_dev(&OsString::from("/")).is_err());
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn main() -> std::process::ExitCode {
+    cli::main()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() -> std::process::ExitCode {
+    eprintln!("tty-info: unsupported platform");
+    std::process::ExitCode::from(3)
+}
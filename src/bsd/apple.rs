@@ -13,15 +13,31 @@ mod sysctl {
     include!(concat!(env!("OUT_DIR"), "/bindings/sysctl.rs"));
 }
 
+pub use bsd::{OwnedTtyInfo, TtyInfo};
 pub use core::ffi::CStr;
-use core::fmt;
 
 pub use bsd_errnos::Errno;
+
+use crate::Error;
+
 /// Device id.
 pub type Dev = u32;
 
+#[cfg(feature = "serde")]
+#[inline]
+pub(crate) fn dev_parts(dev: Dev) -> (u32, u32) {
+    bsd::dev_parts(dev as libc::dev_t)
+}
+
+#[cfg(feature = "serde")]
+#[inline]
+pub(crate) fn dev_from_parts(major: u32, minor: u32) -> Dev {
+    bsd::make_dev(major, minor) as Dev
+}
+
 /// A process' informations useful to get tty informations.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RawProcessInfo {
     /// The process id.
     pub pid: u32,
@@ -30,11 +46,13 @@ pub struct RawProcessInfo {
     /// The group id owning the process.
     pub gid: u32,
     /// The tty device id if process has one.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_dev::option"))]
     pub tty: Option<Dev>,
 }
 
 /// [RawProcessInfo] with `tty` field remapped to [TtyInfo].
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ProcessInfo {
     /// The process id.
     pub pid: u32,
@@ -49,12 +67,12 @@ pub struct ProcessInfo {
 impl RawProcessInfo {
     /// Returns the informations for the current process.
     #[inline]
-    pub fn current() -> Result<Self, Errno> {
+    pub fn current() -> Result<Self, Error> {
         Self::for_process(unsafe { libc::getpid() as u32 })
     }
 
     /// Returns the informations for the `pid` process.
-    pub fn for_process(pid: u32) -> Result<Self, Errno> {
+    pub fn for_process(pid: u32) -> Result<Self, Error> {
         let ki_proc = bsd::proc_info::<sysctl::kinfo_proc>(
             [
                 libc::CTL_KERN,
@@ -81,13 +99,13 @@ impl RawProcessInfo {
 impl ProcessInfo {
     /// Calls [RawProcessInfo::current] and maps `tty` with [TtyInfo::by_device].
     #[inline]
-    pub fn current() -> Result<Self, Errno> {
+    pub fn current() -> Result<Self, Error> {
         ProcessInfo::for_process(unsafe { libc::getpid() as u32 })
     }
 
     /// Calls [RawProcessInfo::for_process] and maps `tty` with [TtyInfo::by_device].
     #[inline]
-    pub fn for_process(pid: u32) -> Result<Self, Errno> {
+    pub fn for_process(pid: u32) -> Result<Self, Error> {
         let info = RawProcessInfo::for_process(pid)?;
 
         Ok(Self {
@@ -98,87 +116,3 @@ impl ProcessInfo {
         })
     }
 }
-
-/// A structure that contains informations about a tty.
-#[derive(Clone)]
-pub struct TtyInfo {
-    nr: Dev,
-    buf: *mut u8,
-}
-
-impl TtyInfo {
-    /// Returns the device number.
-    #[inline]
-    pub const fn device(&self) -> Dev {
-        self.nr
-    }
-
-    /// Returns the device full path.
-    #[inline]
-    pub fn path(&self) -> &CStr {
-        unsafe { CStr::from_ptr(self.buf.cast()) }
-    }
-
-    /// Returns the device full path.
-    #[inline]
-    pub fn name(&self) -> &CStr {
-        unsafe { CStr::from_ptr(self.buf.add(5).cast()) }
-    }
-
-    /// Find a tty by its device number.
-    pub fn by_device(rdev: Dev) -> Result<TtyInfo, Errno> {
-        unsafe {
-            let name = bsd::devname(rdev as _, libc::S_IFCHR);
-            if name.is_null() {
-                return Err(Errno::ENOENT);
-            }
-
-            let name = CStr::from_ptr(name).to_bytes();
-
-            let buf = libc::malloc(5 + name.len() + 1) as *mut u8;
-            if buf.is_null() {
-                return Err(Errno::ENOMEM);
-            }
-            core::ptr::copy_nonoverlapping(b"/dev/".as_ptr().cast(), buf, 5);
-            let ptr = buf.add(5);
-            core::ptr::copy_nonoverlapping(name.as_ptr(), ptr, name.len());
-            *ptr.add(name.len()) = 0;
-
-            Ok(TtyInfo { nr: rdev, buf })
-        }
-    }
-
-    /// Shortcut for [RawProcessInfo::current] + [Self::by_device].
-    #[inline]
-    pub fn current() -> Result<Option<Self>, Errno> {
-        RawProcessInfo::current()?
-            .tty
-            .map(Self::by_device)
-            .transpose()
-    }
-
-    /// Shortcut for [RawProcessInfo::for_process] + [Self::by_device].
-    #[inline]
-    pub fn for_process(pid: u32) -> Result<Option<Self>, Errno> {
-        RawProcessInfo::for_process(pid)?
-            .tty
-            .map(Self::by_device)
-            .transpose()
-    }
-}
-
-impl Drop for TtyInfo {
-    #[inline]
-    fn drop(&mut self) {
-        unsafe { libc::free(self.buf.cast()) };
-    }
-}
-
-impl fmt::Debug for TtyInfo {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TtyInfo")
-            .field("name", &self.name())
-            .field("path", &self.path())
-            .finish()
-    }
-}
@@ -1,13 +1,17 @@
-use crate::Errno;
+use super::{Dev, RawProcessInfo};
+use crate::{Errno, Error, Operation};
 
 use core::{
     borrow::{Borrow, BorrowMut},
+    ffi::CStr,
+    fmt,
+    hash::{Hash, Hasher},
     mem,
     ops::{Deref, DerefMut},
     ptr,
 };
 
-pub fn proc_info<T>(mibs: &mut [libc::c_int]) -> Result<CBox<T>, Errno> {
+pub fn proc_info<T>(mibs: &mut [libc::c_int]) -> Result<CBox<T>, Error> {
     unsafe {
         let mut ki_proc: *mut T = ptr::null_mut();
         let mut size = mem::size_of::<T>();
@@ -43,7 +47,7 @@ pub fn proc_info<T>(mibs: &mut [libc::c_int]) -> Result<CBox<T>, Errno> {
             if !ki_proc.is_null() {
                 libc::free(ki_proc as *mut _);
             }
-            Err(err)
+            Err(Error::new(Operation::Sysctl, err))
         } else {
             Ok(CBox::from_raw(ki_proc))
         }
@@ -112,6 +116,218 @@ impl<T> Drop for CBox<T> {
     }
 }
 
+/// Splits a device id into its major and minor numbers.
+#[cfg(feature = "serde")]
+#[inline]
+pub fn dev_parts(dev: libc::dev_t) -> (u32, u32) {
+    (libc::major(dev) as u32, libc::minor(dev) as u32)
+}
+
+/// Makes a device id from its major and minor numbers.
+#[cfg(feature = "serde")]
+#[inline]
+pub fn make_dev(major: u32, minor: u32) -> libc::dev_t {
+    libc::makedev(major as _, minor as _)
+}
+
 extern "C" {
     pub fn devname(dev: libc::dev_t, r#type: libc::mode_t) -> *const i8;
 }
+
+/// A [TtyInfo] owning its path, which is `Send + Sync` on every backend.
+pub type OwnedTtyInfo = TtyInfo;
+
+/// A structure that contains informations about a tty.
+pub struct TtyInfo {
+    nr: Dev,
+    buf: *mut u8,
+}
+
+// The path is owned, and never mutated once the tty is found.
+unsafe impl Send for TtyInfo {}
+unsafe impl Sync for TtyInfo {}
+
+impl TtyInfo {
+    /// Returns the device number.
+    #[inline]
+    pub const fn device(&self) -> Dev {
+        self.nr
+    }
+
+    /// Returns the device full path.
+    #[inline]
+    pub fn path(&self) -> &CStr {
+        unsafe { CStr::from_ptr(self.buf.cast()) }
+    }
+
+    /// Returns the device full path.
+    #[inline]
+    pub fn name(&self) -> &CStr {
+        unsafe { CStr::from_ptr(self.buf.add(5).cast()) }
+    }
+
+    /// Returns the device full path as a [Path](std::path::Path).
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn as_path(&self) -> &std::path::Path {
+        use std::os::unix::ffi::OsStrExt;
+
+        std::ffi::OsStr::from_bytes(self.path().to_bytes()).as_ref()
+    }
+
+    /// Copies the device full path to a [PathBuf](std::path::PathBuf).
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn to_path_buf(&self) -> std::path::PathBuf {
+        self.as_path().to_path_buf()
+    }
+
+    /// Returns the tty itself, which already owns its path.
+    ///
+    /// Never fails: the `Result` is kept for parity with the Linux backend,
+    /// where copying the path may fail to allocate or to fit `PATH_MAX`.
+    #[inline]
+    pub fn into_owned(self) -> Result<OwnedTtyInfo, Error> {
+        Ok(self)
+    }
+
+    /// Find a tty by its device number.
+    pub fn by_device(rdev: Dev) -> Result<TtyInfo, Error> {
+        unsafe {
+            let name = devname(rdev as _, libc::S_IFCHR);
+            if name.is_null() {
+                return Err(Error::new(Operation::Lookup, Errno::ENOENT));
+            }
+
+            let name = CStr::from_ptr(name).to_bytes();
+
+            let buf = libc::malloc(5 + name.len() + 1) as *mut u8;
+            if buf.is_null() {
+                return Err(Error::new(Operation::Alloc, Errno::ENOMEM));
+            }
+            core::ptr::copy_nonoverlapping(b"/dev/".as_ptr().cast(), buf, 5);
+            let ptr = buf.add(5);
+            core::ptr::copy_nonoverlapping(name.as_ptr(), ptr, name.len());
+            *ptr.add(name.len()) = 0;
+
+            Ok(TtyInfo { nr: rdev, buf })
+        }
+    }
+
+    /// Shortcut for [RawProcessInfo::current] + [Self::by_device].
+    #[inline]
+    pub fn current() -> Result<Option<Self>, Error> {
+        RawProcessInfo::current()?
+            .tty
+            .map(Self::by_device)
+            .transpose()
+    }
+
+    /// Shortcut for [RawProcessInfo::for_process] + [Self::by_device].
+    #[inline]
+    pub fn for_process(pid: u32) -> Result<Option<Self>, Error> {
+        RawProcessInfo::for_process(pid)?
+            .tty
+            .map(Self::by_device)
+            .transpose()
+    }
+}
+
+impl Clone for TtyInfo {
+    fn clone(&self) -> Self {
+        let buf = unsafe { libc::strdup(self.buf.cast()) };
+        if buf.is_null() {
+            alloc_error(self.path().to_bytes_with_nul().len());
+        }
+        TtyInfo {
+            nr: self.nr,
+            buf: buf.cast(),
+        }
+    }
+}
+
+impl Drop for TtyInfo {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { libc::free(self.buf.cast()) };
+    }
+}
+
+/// Displays the path.
+#[cfg(feature = "std")]
+impl fmt::Display for TtyInfo {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_path().display(), f)
+    }
+}
+
+/// Compares the device, then the path.
+impl PartialEq for TtyInfo {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.nr == other.nr && self.path() == other.path()
+    }
+}
+
+impl Eq for TtyInfo {}
+
+impl PartialOrd for TtyInfo {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by device, then by path.
+impl Ord for TtyInfo {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        (self.nr, self.path()).cmp(&(other.nr, other.path()))
+    }
+}
+
+impl Hash for TtyInfo {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nr.hash(state);
+        self.path().hash(state);
+    }
+}
+
+/// Serializes the device, as its major and minor numbers, the path and the
+/// name.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TtyInfo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use crate::serde_dev::{LossyStr, Parts};
+        use serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("TtyInfo", 3)?;
+        s.serialize_field("device", &Parts::from(self.nr))?;
+        s.serialize_field("path", &LossyStr(self.path()))?;
+        s.serialize_field("name", &LossyStr(self.name()))?;
+        s.end()
+    }
+}
+
+impl fmt::Debug for TtyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtyInfo")
+            .field("name", &self.name())
+            .field("path", &self.path())
+            .finish()
+    }
+}
+
+/// Reports a failed allocation of `len` bytes to the allocation error
+/// handler, or panics without the `alloc` feature.
+#[cold]
+fn alloc_error(len: usize) -> ! {
+    #[cfg(feature = "alloc")]
+    crate::alloc_crate::alloc::handle_alloc_error(
+        core::alloc::Layout::from_size_align(len, 1).unwrap(),
+    );
+    #[cfg(not(feature = "alloc"))]
+    panic!("memory allocation of {len} bytes failed");
+}
@@ -0,0 +1,385 @@
+//! C ABI of the library, declared in `include/tty_info.h`.
+//!
+//! Functions returning an `int` return `0` on success and a negated errno on
+//! failure, filling the optional `tty_info_error` with the details. The
+//! dirent buffer used to scan directories is supplied by the caller, a
+//! `NULL` one using a buffer on the stack.
+//!
+//! The `tty-info-capi` package in `capi/` builds it as a static and a
+//! dynamic library (`cargo build --release -p tty-info-capi`).
+
+use core::{
+    ffi::{c_char, c_int},
+    mem::MaybeUninit,
+    ptr,
+};
+
+use crate::{
+    imp::with_default_paths, CBuffer, CStr, Dev, Errno, Error, Operation, RawProcessInfo,
+    SliceBuffer, TtyInfo,
+};
+
+/// Size of the dirent buffer used when the caller supplies none.
+const DEFAULT_DIRENT_BUF_LEN: usize = 4096;
+
+/// Opening a file or directory.
+pub const TTY_INFO_OP_OPEN: c_int = 1;
+/// Reading directory entries.
+pub const TTY_INFO_OP_GETDENTS64: c_int = 2;
+/// Seeking in a directory.
+pub const TTY_INFO_OP_LSEEK: c_int = 3;
+/// Reading file metadata.
+pub const TTY_INFO_OP_FSTATAT: c_int = 4;
+/// Reading a file.
+pub const TTY_INFO_OP_READ: c_int = 5;
+/// Parsing the content of a file.
+pub const TTY_INFO_OP_PARSE: c_int = 6;
+/// Growing a buffer.
+pub const TTY_INFO_OP_ALLOC: c_int = 7;
+/// Searching a device or process which was not found.
+pub const TTY_INFO_OP_LOOKUP: c_int = 8;
+/// Opening a process file descriptor.
+pub const TTY_INFO_OP_PIDFD_OPEN: c_int = 9;
+/// Signaling a process file descriptor.
+pub const TTY_INFO_OP_PIDFD_SEND_SIGNAL: c_int = 10;
+/// Reading the system clock.
+pub const TTY_INFO_OP_CLOCK_GETTIME: c_int = 11;
+/// Querying process informations from the kernel.
+pub const TTY_INFO_OP_SYSCTL: c_int = 12;
+/// Watching a directory for changes.
+pub const TTY_INFO_OP_INOTIFY: c_int = 13;
+/// Opening a socket.
+pub const TTY_INFO_OP_SOCKET: c_int = 14;
+/// Walking a directory tree.
+pub const TTY_INFO_OP_WALK: c_int = 15;
+/// Reading file metadata with `statx`.
+pub const TTY_INFO_OP_STATX: c_int = 16;
+
+/// A tty found by the library, to be released with `tty_info_free`.
+pub struct CTtyInfo {
+    inner: TtyInfo<CBuffer>,
+}
+
+/// Details of a failed call.
+#[repr(C)]
+pub struct CError {
+    /// The failed operation, one of the `TTY_INFO_OP_*` constants.
+    pub operation: c_int,
+    /// The resulting errno.
+    pub errnum: c_int,
+    /// The path the operation was working on, empty if none, keeping its end
+    /// if it was too long.
+    pub path: [c_char; 65],
+}
+
+impl CError {
+    fn write(this: *mut Self, err: &Error) {
+        let this = match unsafe { this.as_mut() } {
+            Some(this) => this,
+            None => return,
+        };
+
+        this.operation = match err.operation() {
+            Operation::Open => TTY_INFO_OP_OPEN,
+            Operation::Getdents64 => TTY_INFO_OP_GETDENTS64,
+            Operation::Lseek => TTY_INFO_OP_LSEEK,
+            Operation::Fstatat => TTY_INFO_OP_FSTATAT,
+            Operation::Read => TTY_INFO_OP_READ,
+            Operation::Parse => TTY_INFO_OP_PARSE,
+            Operation::Alloc => TTY_INFO_OP_ALLOC,
+            Operation::Lookup => TTY_INFO_OP_LOOKUP,
+            Operation::PidfdOpen => TTY_INFO_OP_PIDFD_OPEN,
+            Operation::PidfdSendSignal => TTY_INFO_OP_PIDFD_SEND_SIGNAL,
+            Operation::ClockGettime => TTY_INFO_OP_CLOCK_GETTIME,
+            Operation::Sysctl => TTY_INFO_OP_SYSCTL,
+            Operation::Inotify => TTY_INFO_OP_INOTIFY,
+            Operation::Socket => TTY_INFO_OP_SOCKET,
+            Operation::Walk => TTY_INFO_OP_WALK,
+            Operation::Statx => TTY_INFO_OP_STATX,
+        };
+        this.errnum = err.errno().into_raw();
+
+        let path = err.path().unwrap_or_default();
+        for (dst, &src) in this.path.iter_mut().zip(path) {
+            *dst = src as c_char;
+        }
+        this.path[path.len()] = 0;
+    }
+}
+
+/// Finds `rdev` in the `ndirs` directories of `dirs`, or `/dev` if `dirs` is
+/// `NULL`, and stores it in `out`.
+unsafe fn lookup(
+    rdev: Dev,
+    dirs: *const *const c_char,
+    ndirs: usize,
+    dirent_buf: *mut u8,
+    dirent_buf_len: usize,
+    out: *mut *mut CTtyInfo,
+) -> Result<(), Error> {
+    let mut stack_buf = [MaybeUninit::<u8>::uninit(); DEFAULT_DIRENT_BUF_LEN];
+    let mut buf = if dirent_buf.is_null() {
+        SliceBuffer::from_uninit(&mut stack_buf)
+    } else {
+        SliceBuffer::from_uninit(core::slice::from_raw_parts_mut(
+            dirent_buf.cast(),
+            dirent_buf_len,
+        ))
+    };
+
+    let tty = if dirs.is_null() {
+        with_default_paths(|dirs| {
+            TtyInfo::by_device_with_buffers_in(rdev, dirs, &mut buf, CBuffer::new())
+        })
+    } else {
+        let dirs = (0..ndirs).map(|i| CStr::from_ptr(*dirs.add(i)));
+        TtyInfo::by_device_with_buffers_in(rdev, dirs, &mut buf, CBuffer::new())
+    }?;
+
+    let ptr = libc::malloc(core::mem::size_of::<CTtyInfo>()).cast::<CTtyInfo>();
+    if ptr.is_null() {
+        return Err(Error::new(Operation::Alloc, Errno::ENOMEM));
+    }
+    ptr.write(CTtyInfo { inner: tty });
+    *out = ptr;
+    Ok(())
+}
+
+/// Same as [lookup] for the controlling terminal of `info`, storing `NULL`
+/// if the process has none.
+unsafe fn lookup_process(
+    info: Result<RawProcessInfo, Error>,
+    dirs: *const *const c_char,
+    ndirs: usize,
+    dirent_buf: *mut u8,
+    dirent_buf_len: usize,
+    out: *mut *mut CTtyInfo,
+) -> Result<(), Error> {
+    *out = ptr::null_mut();
+    match info?.tty {
+        Some(rdev) => lookup(rdev, dirs, ndirs, dirent_buf, dirent_buf_len, out),
+        None => Ok(()),
+    }
+}
+
+fn status(res: Result<(), Error>, err: *mut CError) -> c_int {
+    match res {
+        Ok(()) => 0,
+        Err(error) => {
+            CError::write(err, &error);
+            -error.errno().into_raw()
+        }
+    }
+}
+
+/// Finds the tty with device number `major:minor` in the `ndirs` directories
+/// of `dirs`, or in `/dev` if `dirs` is `NULL`.
+///
+/// `dirent_buf` is used as buffer to read directories, a `NULL` one using a
+/// 4096 bytes buffer on the stack. On success, stores the tty in `out`, to be
+/// released with `tty_info_free`. Returns `-ENOTTY` if the device is not a
+/// tty and `-ENOENT` if it was not found.
+///
+/// # Safety
+///
+/// `dirs` must be `NULL` or point to `ndirs` C strings, `dirent_buf` must be
+/// `NULL` or point to `dirent_buf_len` writable bytes, `out` must be valid
+/// for writes and `err` must be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_by_device(
+    major: u32,
+    minor: u32,
+    dirs: *const *const c_char,
+    ndirs: usize,
+    dirent_buf: *mut u8,
+    dirent_buf_len: usize,
+    out: *mut *mut CTtyInfo,
+    err: *mut CError,
+) -> c_int {
+    *out = ptr::null_mut();
+    let rdev = Dev::from_split(major, minor);
+    status(
+        lookup(rdev, dirs, ndirs, dirent_buf, dirent_buf_len, out),
+        err,
+    )
+}
+
+/// Same as `tty_info_by_device` for the controlling terminal of the `pid`
+/// process, storing `NULL` in `out` if it has none.
+///
+/// # Safety
+///
+/// Same as [tty_info_by_device].
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_for_process(
+    pid: u32,
+    dirs: *const *const c_char,
+    ndirs: usize,
+    dirent_buf: *mut u8,
+    dirent_buf_len: usize,
+    out: *mut *mut CTtyInfo,
+    err: *mut CError,
+) -> c_int {
+    let info = RawProcessInfo::for_process(pid);
+    status(
+        lookup_process(info, dirs, ndirs, dirent_buf, dirent_buf_len, out),
+        err,
+    )
+}
+
+/// Same as `tty_info_for_process` for the current process.
+///
+/// # Safety
+///
+/// Same as [tty_info_by_device].
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_current(
+    dirs: *const *const c_char,
+    ndirs: usize,
+    dirent_buf: *mut u8,
+    dirent_buf_len: usize,
+    out: *mut *mut CTtyInfo,
+    err: *mut CError,
+) -> c_int {
+    let info = RawProcessInfo::current();
+    status(
+        lookup_process(info, dirs, ndirs, dirent_buf, dirent_buf_len, out),
+        err,
+    )
+}
+
+/// Returns the full path of `info`, valid until it is freed.
+///
+/// # Safety
+///
+/// `info` must have been returned by this library and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_path(info: *const CTtyInfo) -> *const c_char {
+    (*info).inner.path().as_ptr()
+}
+
+/// Returns the name of `info`, its path relative to the directory it was
+/// found in, valid until it is freed.
+///
+/// # Safety
+///
+/// Same as [tty_info_path].
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_name(info: *const CTtyInfo) -> *const c_char {
+    (*info).inner.name().as_ptr()
+}
+
+/// Returns the major device number of `info`.
+///
+/// # Safety
+///
+/// Same as [tty_info_path].
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_major(info: *const CTtyInfo) -> u32 {
+    (*info).inner.device().major()
+}
+
+/// Returns the minor device number of `info`.
+///
+/// # Safety
+///
+/// Same as [tty_info_path].
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_minor(info: *const CTtyInfo) -> u32 {
+    (*info).inner.device().minor()
+}
+
+/// Releases `info`, doing nothing if it is `NULL`.
+///
+/// # Safety
+///
+/// `info` must be `NULL` or have been returned by this library and not
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn tty_info_free(info: *mut CTtyInfo) {
+    if !info.is_null() {
+        ptr::drop_in_place(info);
+        libc::free(info.cast());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn header_is_up_to_date() {
+        assert_eq!(
+            include_str!(concat!(env!("OUT_DIR"), "/tty_info.h")),
+            include_str!("../include/tty_info.h"),
+            "run `cp $OUT_DIR/tty_info.h include/` after changing the C ABI"
+        );
+    }
+
+    #[test]
+    fn not_a_tty() {
+        let mut out = ptr::null_mut();
+        let mut err = MaybeUninit::<CError>::uninit();
+        let dirs = [c"/dev".as_ptr()];
+        let rc = unsafe {
+            tty_info_by_device(
+                1,
+                3,
+                dirs.as_ptr(),
+                dirs.len(),
+                ptr::null_mut(),
+                0,
+                &mut out,
+                err.as_mut_ptr(),
+            )
+        };
+        let err = unsafe { err.assume_init() };
+        assert_eq!(rc, -Errno::ENOTTY.into_raw());
+        assert!(out.is_null());
+        assert_eq!(err.operation, TTY_INFO_OP_LOOKUP);
+        assert_eq!(err.errnum, Errno::ENOTTY.into_raw());
+        assert_eq!(err.path[0], 0);
+    }
+
+    #[test]
+    fn small_dirent_buffer() {
+        let mut out = ptr::null_mut();
+        let mut buf = [0u8; 16];
+        let rc = unsafe {
+            tty_info_by_device(
+                136,
+                0xfffff,
+                ptr::null(),
+                0,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut out,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(rc, -Errno::ENOMEM.into_raw());
+    }
+
+    #[test]
+    fn missing_process() {
+        let mut out = ptr::null_mut();
+        let mut err = MaybeUninit::<CError>::uninit();
+        let rc = unsafe {
+            tty_info_for_process(
+                u32::MAX,
+                ptr::null(),
+                0,
+                ptr::null_mut(),
+                0,
+                &mut out,
+                err.as_mut_ptr(),
+            )
+        };
+        let err = unsafe { err.assume_init() };
+        assert_eq!(rc, -Errno::ESRCH.into_raw());
+        let path = unsafe { CStr::from_ptr(err.path.as_ptr()) };
+        assert_eq!(path, c"/proc/4294967295/stat");
+    }
+}
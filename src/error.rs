@@ -0,0 +1,346 @@
+use core::fmt;
+
+use crate::Errno;
+
+/// Maximum number of path bytes kept by an [Error].
+const MAX_PATH_LEN: usize = 64;
+
+/// The operation an [Error] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Opening a file or directory.
+    Open,
+    /// Reading directory entries.
+    Getdents64,
+    /// Seeking in a directory.
+    Lseek,
+    /// Reading file metadata.
+    Fstatat,
+    /// Reading a file.
+    Read,
+    /// Parsing the content of a file.
+    Parse,
+    /// Growing a buffer.
+    Alloc,
+    /// Searching a device, process or record which was not found.
+    Lookup,
+    /// Opening a process file descriptor.
+    PidfdOpen,
+    /// Signaling a process file descriptor.
+    PidfdSendSignal,
+    /// Reading the system clock.
+    ClockGettime,
+    /// Querying process informations from the kernel.
+    Sysctl,
+    /// Watching a directory for changes.
+    Inotify,
+    /// Opening a socket.
+    Socket,
+    /// Walking a directory tree.
+    Walk,
+    /// Reading file metadata with `statx`.
+    Statx,
+}
+
+impl Operation {
+    /// Returns the operation name.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Getdents64 => "getdents64",
+            Self::Lseek => "lseek",
+            Self::Fstatat => "fstatat",
+            Self::Read => "read",
+            Self::Parse => "parse",
+            Self::Alloc => "alloc",
+            Self::Lookup => "lookup",
+            Self::PidfdOpen => "pidfd_open",
+            Self::PidfdSendSignal => "pidfd_send_signal",
+            Self::ClockGettime => "clock_gettime",
+            Self::Sysctl => "sysctl",
+            Self::Inotify => "inotify",
+            Self::Socket => "socket",
+            Self::Walk => "walk",
+            Self::Statx => "statx",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The error type of the crate: the failed [Operation], the path it was
+/// operating on if any and the resulting [Errno].
+///
+/// Paths longer than 64 bytes are stored truncated, keeping their end.
+#[derive(Clone, Copy)]
+pub struct Error {
+    operation: Operation,
+    errno: Errno,
+    path: [u8; MAX_PATH_LEN],
+    path_len: u8,
+    has_path: bool,
+    truncated: bool,
+}
+
+impl Error {
+    /// Creates a new error without path.
+    #[inline]
+    pub const fn new(operation: Operation, errno: Errno) -> Self {
+        Self {
+            operation,
+            errno,
+            path: [0; MAX_PATH_LEN],
+            path_len: 0,
+            has_path: false,
+            truncated: false,
+        }
+    }
+
+    /// Creates a new error operating on `path`.
+    #[inline]
+    pub fn with_path(operation: Operation, path: &[u8], errno: Errno) -> Self {
+        Self::new(operation, errno).or_path(path)
+    }
+
+    /// Sets the path to `path` if the error has none yet.
+    pub(crate) fn or_path(mut self, path: &[u8]) -> Self {
+        if !self.has_path {
+            self.set_path(&[path]);
+        }
+        self
+    }
+
+    /// Prefixes the path, relative to `dir`, with `dir` or sets it to `dir`
+    /// if the error has none yet.
+    pub(crate) fn in_dir(mut self, dir: &[u8]) -> Self {
+        if !self.has_path {
+            self.set_path(&[dir]);
+        } else if !self.truncated {
+            let (path, len) = (self.path, self.path_len as usize);
+            self.set_path(&[dir, b"/", &path[..len]]);
+        }
+        self
+    }
+
+    /// Sets the path to the concatenation of `parts`, keeping its end if
+    /// it is too long.
+    fn set_path(&mut self, parts: &[&[u8]]) {
+        fn trim(part: &[u8]) -> &[u8] {
+            part.strip_suffix(b"\0").unwrap_or(part)
+        }
+
+        let total = parts.iter().map(|part| trim(part).len()).sum::<usize>();
+        let mut skip = total.saturating_sub(MAX_PATH_LEN);
+        let mut len = 0;
+
+        self.truncated = skip != 0;
+        self.has_path = true;
+        for part in parts {
+            let part = trim(part);
+            let start = core::cmp::min(skip, part.len());
+            skip -= start;
+            let part = &part[start..];
+            self.path[len..(len + part.len())].copy_from_slice(part);
+            len += part.len();
+        }
+        self.path_len = len as u8;
+    }
+
+    /// Returns the failed operation.
+    #[inline]
+    pub const fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Returns the error number.
+    #[inline]
+    pub const fn errno(&self) -> Errno {
+        self.errno
+    }
+
+    /// Returns the path the operation failed on, if any.
+    ///
+    /// See [Self::is_path_truncated].
+    #[inline]
+    pub fn path(&self) -> Option<&[u8]> {
+        if self.has_path {
+            Some(&self.path[..self.path_len as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if [Self::path] only holds the end of the path.
+    #[inline]
+    pub const fn is_path_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns `true` if the error is due to the looked up process having
+    /// exited, whatever step of the lookup it was detected at.
+    ///
+    /// Such errors are reported as an [Operation::Lookup] with
+    /// [Errno::ESRCH].
+    #[inline]
+    pub fn is_process_gone(&self) -> bool {
+        self.operation == Operation::Lookup && self.errno == Errno::ESRCH
+    }
+
+    /// Turns the error into a process gone one, keeping its path.
+    #[inline]
+    pub(crate) fn process_gone(self) -> Self {
+        Self {
+            operation: Operation::Lookup,
+            errno: Errno::ESRCH,
+            ..self
+        }
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.operation == other.operation
+            && self.errno == other.errno
+            && self.path() == other.path()
+            && self.truncated == other.truncated
+    }
+}
+
+impl Eq for Error {}
+
+impl core::hash::Hash for Error {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.operation.hash(state);
+        self.errno.hash(state);
+        self.path().hash(state);
+        self.truncated.hash(state);
+    }
+}
+
+/// Formats a path escaping non printable bytes.
+struct DisplayPath<'a>(&'a [u8], bool);
+
+impl fmt::Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.1 {
+            f.write_str("...")?;
+        }
+        fmt::Display::fmt(&self.0.escape_ascii(), f)
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Error");
+        s.field("operation", &self.operation);
+        if let Some(path) = self.path() {
+            s.field(
+                "path",
+                &format_args!("\"{}\"", DisplayPath(path, self.truncated)),
+            );
+        }
+        s.field("errno", &self.errno).finish()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.operation.as_str())?;
+        if let Some(path) = self.path() {
+            write!(f, " `{}`", DisplayPath(path, self.truncated))?;
+        }
+        match (self.errno.name(), self.errno.description()) {
+            (Some(name), Some(desc)) => write!(f, ": {desc} ({name})"),
+            _ => write!(f, ": {}", self.errno),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Converts to an [std::io::Error] of the errno kind, keeping the [Error]
+/// as its inner error.
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    #[inline]
+    fn from(value: Error) -> Self {
+        let kind = std::io::Error::from_raw_os_error(value.errno.into_raw()).kind();
+        std::io::Error::new(kind, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn error_path() {
+        let err = Error::new(Operation::Read, Errno::EIO);
+        assert_eq!(err.path(), None);
+
+        let err = err.or_path(b"/proc/42/stat\0").or_path(b"/dev");
+        assert_eq!(err.path(), Some(&b"/proc/42/stat"[..]));
+        assert!(!err.is_path_truncated());
+
+        let long = [b'a'; 100];
+        let err = Error::with_path(Operation::Open, &long, Errno::ENOENT);
+        assert_eq!(err.path(), Some(&long[..MAX_PATH_LEN]));
+        assert!(err.is_path_truncated());
+
+        let err = Error::with_path(Operation::Fstatat, b"pts/3", Errno::EACCES).in_dir(b"/dev");
+        assert_eq!(err.path(), Some(&b"/dev/pts/3"[..]));
+        let err = Error::new(Operation::Getdents64, Errno::EIO).in_dir(b"/dev");
+        assert_eq!(err.path(), Some(&b"/dev"[..]));
+
+        let err = Error::with_path(Operation::Open, b"tty1", Errno::ENOENT).in_dir(&long);
+        assert!(err.is_path_truncated());
+        assert!(err.path().unwrap().ends_with(b"aaa/tty1"));
+        assert_eq!(err.path().unwrap().len(), MAX_PATH_LEN);
+    }
+
+    #[test]
+    fn process_gone() {
+        let err = Error::with_path(Operation::Parse, b"/proc/42/stat", Errno::ENODATA);
+        assert!(!err.is_process_gone());
+
+        let err = err.process_gone();
+        assert!(err.is_process_gone());
+        assert_eq!(err.operation(), Operation::Lookup);
+        assert_eq!(err.path(), Some(&b"/proc/42/stat"[..]));
+
+        // Only lookups are process gone errors, not any failing with ESRCH.
+        assert!(!Error::new(Operation::Read, Errno::ESRCH).is_process_gone());
+    }
+
+    #[test]
+    fn error_display() {
+        let err = Error::with_path(Operation::Open, b"/proc/42/stat", Errno::ENOENT);
+        assert_eq!(
+            err.to_string(),
+            "open `/proc/42/stat`: No such file or directory (ENOENT)"
+        );
+        assert_eq!(
+            Error::new(Operation::Getdents64, Errno::EIO).to_string(),
+            "getdents64: I/O error (EIO)"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn error_into_io_error() {
+        let err = Error::with_path(Operation::Open, b"/proc/42/stat", Errno::ENOENT);
+        let io = std::io::Error::from(err);
+        assert_eq!(io.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(io.get_ref().unwrap().downcast_ref::<Error>(), Some(&err));
+    }
+}
@@ -1,12 +1,8 @@
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
-#[cfg(all(
-    any(target_os = "linux", target_os = "android"),
-    feature = "alloc",
-    not(feature = "std")
-))]
+#[cfg(all(feature = "alloc", not(feature = "std")))]
 extern crate alloc as alloc_crate;
-#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+#[cfg(feature = "std")]
 extern crate std as alloc_crate;
 
 #[cfg_attr(any(target_os = "linux", target_os = "android"), path = "linux/mod.rs")]
@@ -29,4 +25,13 @@ extern crate std as alloc_crate;
 )]
 mod imp;
 
+#[cfg(all(feature = "capi", any(target_os = "linux", target_os = "android")))]
+pub mod capi;
+mod error;
+#[cfg(feature = "serde")]
+pub mod serde_dev;
+#[cfg(all(feature = "tokio", any(target_os = "linux", target_os = "android")))]
+pub mod tokio;
+
+pub use error::*;
 pub use imp::*;
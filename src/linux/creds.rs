@@ -0,0 +1,199 @@
+use crate::{CStr, Errno, Error, Operation, ProcessHandle};
+use atoi::FromRadix10Checked;
+use linux_stat::CURRENT_DIRECTORY;
+
+use super::procfs::{check_gone, with_proc_path, File};
+
+/// Value of `loginuid` and `sessionid` when they have not been set.
+const AUDIT_UNSET: u32 = u32::MAX;
+
+/// The real, effective, saved and filesystem variants of a user or group id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ids {
+    /// The real id.
+    pub real: u32,
+    /// The effective id.
+    pub effective: u32,
+    /// The saved set id.
+    pub saved: u32,
+    /// The filesystem id.
+    pub filesystem: u32,
+}
+
+impl Ids {
+    /// Parses the tab separated values of the `Uid` and `Gid` fields of
+    /// `/proc/<pid>/status`.
+    fn parse(buf: &[u8]) -> Result<Self, Errno> {
+        fn next(buf: &[u8]) -> Result<(u32, &[u8]), Errno> {
+            match u32::from_radix_10_checked(buf) {
+                (Some(id), len) if len != 0 => Ok((id, &buf[len..])),
+                _ => Err(Errno::EINVAL),
+            }
+        }
+
+        fn skip_tab(buf: &[u8]) -> Result<&[u8], Errno> {
+            buf.strip_prefix(b"\t").ok_or(Errno::EINVAL)
+        }
+
+        let (real, buf) = next(buf)?;
+        let (effective, buf) = next(skip_tab(buf)?)?;
+        let (saved, buf) = next(skip_tab(buf)?)?;
+        let (filesystem, buf) = next(skip_tab(buf)?)?;
+        if !buf.is_empty() {
+            return Err(Errno::EINVAL);
+        }
+
+        Ok(Self {
+            real,
+            effective,
+            saved,
+            filesystem,
+        })
+    }
+}
+
+/// A process' credentials and audit login identity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    /// The user ids, from `/proc/<pid>/status`.
+    pub uid: Ids,
+    /// The group ids, from `/proc/<pid>/status`.
+    pub gid: Ids,
+    /// The user id the process' login session was started by, from
+    /// `/proc/<pid>/loginuid`, if set and supported by the kernel.
+    pub login_uid: Option<u32>,
+    /// The audit session id, from `/proc/<pid>/sessionid`, if set and
+    /// supported by the kernel.
+    pub audit_session: Option<u32>,
+}
+
+impl Credentials {
+    fn parse(pid: Option<u32>) -> Result<Self, Error> {
+        let (uid, gid) = with_proc_path(pid, b"status", |path| {
+            let mut uid = None;
+            let mut gid = None;
+
+            File::open_at(CURRENT_DIRECTORY, path)?
+                .for_each_field(|key, value| {
+                    match key {
+                        b"Uid" => uid = Some(Ids::parse(value)),
+                        b"Gid" => gid = Some(Ids::parse(value)),
+                        _ => (),
+                    }
+                    uid.is_none() || gid.is_none()
+                })
+                .map_err(|err| err.or_path(path.to_bytes()))?;
+
+            let parse_error = |err| Error::with_path(Operation::Parse, path.to_bytes(), err);
+            match (uid, gid) {
+                (Some(uid), Some(gid)) => {
+                    Ok((uid.map_err(parse_error)?, gid.map_err(parse_error)?))
+                }
+                _ => Err(parse_error(Errno::EINVAL)),
+            }
+        })?;
+
+        Ok(Self {
+            uid,
+            gid,
+            login_uid: with_proc_path(pid, b"loginuid", read_audit_id)?,
+            audit_session: with_proc_path(pid, b"sessionid", read_audit_id)?,
+        })
+    }
+
+    /// Returns the credentials of the current process.
+    #[inline]
+    pub fn current() -> Result<Self, Error> {
+        Self::parse(None)
+    }
+
+    /// Returns the credentials of the `pid` process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process does not exist or exited while being read.
+    #[inline]
+    pub fn for_process(pid: u32) -> Result<Self, Error> {
+        Self::parse(Some(pid)).map_err(|err| check_gone(pid, err))
+    }
+
+    /// Returns the credentials of the process referred by `handle`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process exited.
+    #[inline]
+    pub fn for_handle(handle: &ProcessHandle) -> Result<Self, Error> {
+        handle.with_pid(Self::for_process)
+    }
+}
+
+/// Reads an audit id file, returning [None] if it is unset or the kernel has
+/// been built without audit support.
+fn read_audit_id(path: &CStr) -> Result<Option<u32>, Error> {
+    let file = match File::open_at(CURRENT_DIRECTORY, path) {
+        Err(err) if err.errno() == Errno::ENOENT => return Ok(None),
+        other => other?,
+    };
+
+    let mut buf = [0u8; 16];
+    let buf = file
+        .read_to_fill(&mut buf)
+        .map_err(|err| err.or_path(path.to_bytes()))?;
+    parse_audit_id(buf).map_err(|err| Error::with_path(Operation::Parse, path.to_bytes(), err))
+}
+
+fn parse_audit_id(buf: &[u8]) -> Result<Option<u32>, Errno> {
+    let buf = buf.strip_suffix(b"\n").unwrap_or(buf);
+    match u32::from_radix_10_checked(buf) {
+        (Some(id), len) if len != 0 && len == buf.len() => {
+            Ok(if id == AUDIT_UNSET { None } else { Some(id) })
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn parse_ids() {
+        let ids = Ids::parse(b"1000\t0\t1001\t1002").unwrap();
+        assert_eq!(
+            ids,
+            Ids {
+                real: 1000,
+                effective: 0,
+                saved: 1001,
+                filesystem: 1002
+            }
+        );
+        assert!(Ids::parse(b"1000\t0\t1001").is_err());
+        assert!(Ids::parse(b"1000\t0\t1001\t1002\t").is_err());
+        assert!(Ids::parse(b"1000 0 1001 1002").is_err());
+        assert!(Ids::parse(b"\t0\t1001\t1002").is_err());
+        assert!(Ids::parse(b"4294967296\t0\t1001\t1002").is_err());
+    }
+
+    #[test]
+    fn parse_audit_ids() {
+        assert_eq!(parse_audit_id(b"1000"), Ok(Some(1000)));
+        assert_eq!(parse_audit_id(b"0\n"), Ok(Some(0)));
+        assert_eq!(parse_audit_id(b"4294967295"), Ok(None));
+        assert!(parse_audit_id(b"").is_err());
+        assert!(parse_audit_id(b"-1").is_err());
+    }
+
+    #[test]
+    fn current_credentials() {
+        let creds = Credentials::current().unwrap();
+        let status = std::fs::read_to_string("/proc/self/status").unwrap();
+        let uid = status.lines().find(|l| l.starts_with("Uid:")).unwrap();
+        assert!(uid.contains(&std::format!("\t{}\t", creds.uid.effective)));
+    }
+}
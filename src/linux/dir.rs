@@ -2,18 +2,95 @@ use core::{
     borrow::{Borrow, BorrowMut},
     mem::{ManuallyDrop, MaybeUninit},
     ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 pub use crate::{CStr, Errno, RawFd};
 
-use linux_raw_sys::general::{O_CLOEXEC, O_DIRECTORY, O_RDONLY, SEEK_SET};
-use linux_stat::CURRENT_DIRECTORY;
+use crate::{Dev, Error, Operation, Stat};
+
+use linux_raw_sys::general::{
+    open_how, statx, AT_STATX_DONT_SYNC, AT_SYMLINK_NOFOLLOW, O_CLOEXEC, O_DIRECTORY, O_NOFOLLOW,
+    O_RDONLY, RESOLVE_BENEATH, RESOLVE_IN_ROOT, RESOLVE_NO_MAGICLINKS, RESOLVE_NO_SYMLINKS,
+    SEEK_SET, STATX_TYPE, S_IFMT,
+};
+use linux_stat::{fstatat_cstr, StatAtFlags, CURRENT_DIRECTORY};
 use linux_syscalls::{syscall, Sysno};
 
+#[cfg(feature = "allocator-api2")]
+use allocator_api2::alloc::{Allocator, Global};
+
+/// Initial dirent buffer size when the given one is too small for a single entry.
+const MIN_DIRENT_BUF_LEN: usize = 1024;
+
+/// Cleared once `statx` turned out to be missing from the running kernel.
+static HAS_STATX: AtomicBool = AtomicBool::new(true);
+
+/// Cleared once `openat2` turned out to be missing from the running kernel or
+/// denied by a seccomp filter.
+static HAS_OPENAT2: AtomicBool = AtomicBool::new(true);
+
+/// How [Dir::open_at_with] resolves paths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resolve {
+    /// Follows links and `..` anywhere, as `openat`.
+    #[default]
+    Any,
+    /// Keeps the resolution beneath the directory: absolute paths, and `..`
+    /// or links leading out of it, fail with [Errno::EXDEV]. Magic links, as
+    /// the ones in `/proc/<pid>/fd`, fail with [Errno::ELOOP].
+    Beneath,
+    /// Resolves as if the directory was the root: absolute paths, `..` and
+    /// absolute links are anchored to it. Magic links fail with
+    /// [Errno::ELOOP].
+    InRoot,
+    /// Like [Resolve::Beneath], but any link fails with [Errno::ELOOP].
+    NoSymlinks,
+}
+
+/// The file type and device number of a file, as read by
+/// [Dir::device_metadata_at].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceMetadata {
+    file_type: DirentFileType,
+    rdev: Dev,
+}
+
+impl DeviceMetadata {
+    /// Returns the file type.
+    #[inline]
+    pub const fn file_type(&self) -> DirentFileType {
+        self.file_type
+    }
+
+    /// Returns `true` if the file is a character device.
+    #[inline]
+    pub fn is_char(&self) -> bool {
+        self.file_type == DirentFileType::Character
+    }
+
+    /// Returns the device number of a device file.
+    #[inline]
+    pub const fn rdev(&self) -> Dev {
+        self.rdev
+    }
+}
+
+impl From<Stat> for DeviceMetadata {
+    #[inline]
+    fn from(md: Stat) -> Self {
+        Self {
+            file_type: md.file_type().into(),
+            rdev: md.rdev(),
+        }
+    }
+}
+
 /// An object providing access to an open directory on the filesystem.
 ///
 /// Dirs are automatically closed when they go out of scope.
 /// Errors detected on closing are ignored by the implementation of Drop.
+#[derive(Debug)]
 pub struct Dir {
     fd: RawFd,
     tell: u64,
@@ -21,29 +98,66 @@ pub struct Dir {
 
 impl Dir {
     /// Attempts to open a directory by a `path` relative to `dir`.
-    pub fn open_at(dir: &Dir, path: &CStr) -> Result<Self, Errno> {
-        let dir = dir.as_raw_fd();
-        let path = path.as_ptr();
-        let flags = O_RDONLY | O_DIRECTORY | O_CLOEXEC;
+    #[inline]
+    pub fn open_at(dir: &Dir, path: &CStr) -> Result<Self, Error> {
+        openat(dir.fd, path, 0)
+            .map_err(|err| Error::with_path(Operation::Open, path.to_bytes(), err))
+    }
 
-        loop {
-            match unsafe { syscall!([ro] Sysno::openat, dir, path, flags, 0o666) } {
-                Err(Errno::EINTR) => (),
-                Err(err) => return Err(err),
-                Ok(x) => {
-                    return Ok(Dir {
-                        fd: x as RawFd,
-                        tell: 0,
-                    })
+    /// Attempts to open a directory by a `path` relative to `dir`, resolved
+    /// according to `resolve`.
+    ///
+    /// Uses `openat2`. On older kernels, or when a seccomp filter denies it,
+    /// the path is instead opened one component at a time: links then fail
+    /// with [Errno::ENOTDIR] and `..` with [Errno::EXDEV].
+    pub fn open_at_with(dir: &Dir, path: &CStr, resolve: Resolve) -> Result<Self, Error> {
+        let flags = match resolve {
+            Resolve::Any => return Self::open_at(dir, path),
+            Resolve::Beneath => RESOLVE_BENEATH | RESOLVE_NO_MAGICLINKS,
+            Resolve::InRoot => RESOLVE_IN_ROOT | RESOLVE_NO_MAGICLINKS,
+            Resolve::NoSymlinks => RESOLVE_BENEATH | RESOLVE_NO_SYMLINKS,
+        };
+
+        if HAS_OPENAT2.load(Ordering::Relaxed) {
+            let how = open_how {
+                flags: (O_RDONLY | O_DIRECTORY | O_CLOEXEC) as u64,
+                mode: 0,
+                resolve: flags as u64,
+            };
+            loop {
+                match unsafe {
+                    syscall!(
+                        [ro] Sysno::openat2,
+                        dir.fd,
+                        path.as_ptr(),
+                        &how as *const open_how,
+                        core::mem::size_of::<open_how>()
+                    )
+                } {
+                    // `EAGAIN` reports a concurrent rename or mount.
+                    Err(Errno::EINTR | Errno::EAGAIN) => (),
+                    // Seccomp filters of container runtimes deny syscalls
+                    // unknown to them with `EPERM`.
+                    Err(Errno::ENOSYS | Errno::EPERM) => {
+                        HAS_OPENAT2.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                    Err(err) => {
+                        return Err(Error::with_path(Operation::Open, path.to_bytes(), err))
+                    }
+                    Ok(fd) => return Ok(unsafe { Self::from_raw_fd(fd as RawFd) }),
                 }
             }
         }
+
+        open_components(dir, path, resolve == Resolve::InRoot)
+            .map_err(|err| Error::with_path(Operation::Open, path.to_bytes(), err))
     }
 
     /// Attempts to open a directory by a `path` relative to
     /// current working directory.
     #[inline]
-    pub fn open(file: &CStr) -> Result<Self, Errno> {
+    pub fn open(file: &CStr) -> Result<Self, Error> {
         let dir = ManuallyDrop::new(unsafe { Dir::from_raw_fd(CURRENT_DIRECTORY) });
         Self::open_at(&dir, file)
     }
@@ -64,16 +178,132 @@ impl Dir {
         self.fd
     }
 
+    /// Reads the metadata of `name`, relative to the directory, without
+    /// following links.
+    pub fn metadata_at(&self, name: &CStr) -> Result<Stat, Error> {
+        loop {
+            match unsafe { fstatat_cstr(self.fd, name, StatAtFlags::SYMLINK_NOFOLLOW) } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::with_path(Operation::Fstatat, name.to_bytes(), err)),
+                Ok(md) => return Ok(md),
+            }
+        }
+    }
+
+    /// Reads the file type and the device number of `name`, relative to the
+    /// directory, without following links.
+    ///
+    /// Uses `statx` with `AT_STATX_DONT_SYNC`, requesting only the file type,
+    /// which spares network and FUSE filesystems a round trip to the server.
+    /// Falls back to [Dir::metadata_at] on kernels without `statx`.
+    pub fn device_metadata_at(&self, name: &CStr) -> Result<DeviceMetadata, Error> {
+        if !HAS_STATX.load(Ordering::Relaxed) {
+            return self.metadata_at(name).map(DeviceMetadata::from);
+        }
+
+        let flags = AT_SYMLINK_NOFOLLOW | AT_STATX_DONT_SYNC;
+        let mut buf = MaybeUninit::<statx>::uninit();
+        loop {
+            match unsafe {
+                syscall!(
+                    Sysno::statx,
+                    self.fd,
+                    name.as_ptr(),
+                    flags,
+                    STATX_TYPE,
+                    buf.as_mut_ptr()
+                )
+            } {
+                Err(Errno::EINTR) => (),
+                Err(Errno::ENOSYS) => {
+                    HAS_STATX.store(false, Ordering::Relaxed);
+                    return self.metadata_at(name).map(DeviceMetadata::from);
+                }
+                Err(err) => return Err(Error::with_path(Operation::Statx, name.to_bytes(), err)),
+                Ok(_) => break,
+            }
+        }
+
+        let buf = unsafe { buf.assume_init() };
+        Ok(DeviceMetadata {
+            file_type: DirentFileType::from_raw(((buf.stx_mode as u32 & S_IFMT) >> 12) as u8),
+            rdev: Dev::from_split(buf.stx_rdev_major, buf.stx_rdev_minor),
+        })
+    }
+
+    /// Moves back to the position after the last entry read.
+    fn seek(&self) -> Result<(), Error> {
+        loop {
+            match unsafe { syscall!([ro] Sysno::lseek, self.fd, self.tell, SEEK_SET) } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::new(Operation::Lseek, err)),
+                Ok(_) => return Ok(()),
+            }
+        }
+    }
+
     /// Constructs a new [DirIterator].
     #[inline]
     pub fn iter<'a, B: DirentBuf>(
         &'a mut self,
         buf: &'a mut B,
-    ) -> Result<DirIterator<'a, B>, Errno> {
+    ) -> Result<DirIterator<'a, B>, Error> {
         DirIterator::new(self, buf)
     }
 }
 
+/// Opens the directory `path` relative to `dirfd`.
+fn openat(dirfd: RawFd, path: &CStr, flags: u32) -> Result<Dir, Errno> {
+    let flags = O_RDONLY | O_DIRECTORY | O_CLOEXEC | flags;
+
+    loop {
+        match unsafe { syscall!([ro] Sysno::openat, dirfd, path.as_ptr(), flags, 0o666) } {
+            Err(Errno::EINTR) => (),
+            Err(err) => return Err(err),
+            Ok(fd) => return Ok(unsafe { Dir::from_raw_fd(fd as RawFd) }),
+        }
+    }
+}
+
+/// Opens the directory `path` relative to `dir` one component at a time,
+/// failing on links and on `..`.
+fn open_components(dir: &Dir, path: &CStr, in_root: bool) -> Result<Dir, Errno> {
+    let mut path = path.to_bytes();
+    if path.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+    if path[0] == b'/' {
+        if !in_root {
+            return Err(Errno::EXDEV);
+        }
+        while let [b'/', rest @ ..] = path {
+            path = rest;
+        }
+    }
+
+    let mut current = None::<Dir>;
+    let mut name = [0u8; NAME_MAX + 1];
+    for component in path.split(|&c| c == b'/') {
+        match component {
+            b"" | b"." => continue,
+            b".." => return Err(Errno::EXDEV),
+            _ if component.len() > NAME_MAX => return Err(Errno::ENAMETOOLONG),
+            _ => (),
+        }
+        name[..component.len()].copy_from_slice(component);
+        name[component.len()] = 0;
+        let name = unsafe { CStr::from_bytes_with_nul_unchecked(&name[..=component.len()]) };
+
+        let parent = current.as_ref().map_or(dir.fd, Dir::as_raw_fd);
+        current = Some(openat(parent, name, O_NOFOLLOW)?);
+    }
+
+    match current {
+        Some(dir) => Ok(dir),
+        None => openat(dir.fd, c".", 0),
+    }
+}
+
 impl Drop for Dir {
     fn drop(&mut self) {
         _ = unsafe { syscall!([ro] Sysno::close, self.fd) };
@@ -164,7 +394,6 @@ pub trait DirentBuf:
 #[repr(u8)]
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[allow(dead_code)]
 pub enum DirentFileType {
     Unknown = 0,
     /// FIFO pipe.
@@ -184,6 +413,38 @@ pub enum DirentFileType {
     Wht = 14,
 }
 
+impl DirentFileType {
+    /// Converts the `d_type` field of a dirent, unknown values included.
+    const fn from_raw(value: u8) -> Self {
+        match value {
+            1 => Self::Fifo,
+            2 => Self::Character,
+            4 => Self::Directory,
+            6 => Self::Block,
+            8 => Self::Regular,
+            10 => Self::Link,
+            12 => Self::Socket,
+            14 => Self::Wht,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<linux_stat::FileType> for DirentFileType {
+    fn from(value: linux_stat::FileType) -> Self {
+        match value {
+            linux_stat::FileType::Fifo => Self::Fifo,
+            linux_stat::FileType::Character => Self::Character,
+            linux_stat::FileType::Directory => Self::Directory,
+            linux_stat::FileType::Block => Self::Block,
+            linux_stat::FileType::Regular => Self::Regular,
+            linux_stat::FileType::Link => Self::Link,
+            linux_stat::FileType::Socket => Self::Socket,
+            _ => Self::Unknown,
+        }
+    }
+}
+
 impl From<DirentFileType> for linux_stat::FileType {
     fn from(value: DirentFileType) -> Self {
         match value {
@@ -199,97 +460,41 @@ impl From<DirentFileType> for linux_stat::FileType {
     }
 }
 
-/// A type representing a directory entry, returned by [DirIterator].
-#[repr(packed)]
-#[allow(dead_code)]
-pub struct DirEntry {
+/// Size of the fixed part of a `linux_dirent64`, before the name.
+const DIRENT_HEADER_LEN: usize = 19;
+
+/// Maximum length of a file name.
+pub(crate) const NAME_MAX: usize = 255;
+
+/// A directory entry, returned by [DirIterator::next].
+///
+/// It borrows the dirent buffer, which the iterator overwrites when it reads
+/// the next entries; convert it to a [DirEntryOwned] to keep it.
+#[derive(Debug, Clone, Copy)]
+pub struct DirEntry<'a> {
     ino: u64,
     off: u64,
-    reclen: core::ffi::c_ushort,
-    r#type: DirentFileType,
-    name: [u8; 0],
-}
-
-/// An iterator over a filesystem directory.
-pub struct DirIterator<'a, B: DirentBuf> {
-    dir: &'a mut Dir,
-    buf: &'a mut B,
-    offset: usize,
+    reclen: u16,
+    file_type: DirentFileType,
+    name: &'a CStr,
 }
 
-impl<'a, B: DirentBuf> DirIterator<'a, B> {
-    /// Creates a new iterator over directory `dir` using `buf` as a buffer.
-    #[inline]
-    pub fn new(dir: &'a mut Dir, buf: &'a mut B) -> Result<Self, Errno> {
-        if dir.tell != 0 {
-            loop {
-                match unsafe { syscall!([ro] Sysno::lseek, dir.fd, dir.tell, SEEK_SET) } {
-                    Err(Errno::EINTR) => (),
-                    Err(err) => return Err(err),
-                    Ok(_) => break,
-                }
-            }
-        }
-
-        Ok(Self {
-            dir,
-            buf,
-            offset: 0,
+impl<'a> DirEntry<'a> {
+    /// Reads the `linux_dirent64` at the start of `buf`.
+    fn parse(buf: &'a [u8]) -> Option<Self> {
+        let header = buf.get(..DIRENT_HEADER_LEN)?;
+        let reclen = u16::from_ne_bytes([header[16], header[17]]);
+        let name = buf.get(DIRENT_HEADER_LEN..reclen as usize)?;
+
+        Some(Self {
+            ino: u64::from_ne_bytes(header[..8].try_into().ok()?),
+            off: u64::from_ne_bytes(header[8..16].try_into().ok()?),
+            reclen,
+            file_type: DirentFileType::from_raw(header[18]),
+            name: CStr::from_bytes_until_nul(name).ok()?,
         })
     }
 
-    fn buffer(&self) -> &[u8] {
-        let len = self.buf.len() - self.offset;
-        unsafe { core::slice::from_raw_parts(self.buf.as_ptr().add(self.offset), len) }
-    }
-}
-
-impl<'a, B: DirentBuf> Iterator for DirIterator<'a, B> {
-    type Item = Result<&'a DirEntry, Errno>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        #[inline(always)]
-        unsafe fn getdents64<B: DirentBuf>(fd: RawFd, buf: &mut B) -> Result<(), Errno> {
-            buf.reset();
-            loop {
-                match syscall!(Sysno::getdents64, fd, buf.as_mut_ptr(), buf.capacity()) {
-                    Err(Errno::EINVAL) => {
-                        buf.reserve(buf.capacity() * 3 / 2)?;
-                    }
-                    Err(Errno::EINTR) => (),
-                    Err(err) => return Err(err),
-                    Ok(len) => {
-                        buf.set_len(len);
-                        return Ok(());
-                    }
-                }
-            }
-        }
-
-        unsafe {
-            let mut buf = self.buffer();
-
-            if buf.len() < core::mem::size_of::<DirEntry>() {
-                self.offset = 0;
-                if let Err(err) = getdents64(self.dir.fd, self.buf) {
-                    return Some(Err(err));
-                }
-                buf = self.buffer();
-            }
-
-            if buf.len() < core::mem::size_of::<DirEntry>() {
-                None
-            } else {
-                let res: &'a DirEntry = &*(buf.as_ptr().cast());
-                self.offset += res.len();
-                self.dir.tell = res.offset();
-                Some(Ok(res))
-            }
-        }
-    }
-}
-
-impl DirEntry {
     /// Returns the inode for the entry.
     #[inline]
     pub const fn inode(&self) -> u64 {
@@ -305,13 +510,13 @@ impl DirEntry {
     /// Returns the file type for the entry.
     #[inline]
     pub const fn file_type(&self) -> DirentFileType {
-        self.r#type
+        self.file_type
     }
 
     /// Returns the file name for the entry.
     #[inline]
-    pub fn name(&self) -> &CStr {
-        unsafe { CStr::from_ptr(self.name.as_ptr().cast()) }
+    pub const fn name(&self) -> &'a CStr {
+        self.name
     }
 
     /// Returns the total size of the entry.
@@ -327,18 +532,263 @@ impl DirEntry {
     }
 }
 
+/// An owned copy of a [DirEntry], which does not borrow the dirent buffer.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct DirEntryOwned {
+    ino: u64,
+    file_type: DirentFileType,
+    name: [u8; NAME_MAX + 1],
+    name_len: usize,
+}
+
+impl DirEntryOwned {
+    /// Returns the inode for the entry.
+    #[inline]
+    pub const fn inode(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the file type for the entry.
+    #[inline]
+    pub const fn file_type(&self) -> DirentFileType {
+        self.file_type
+    }
+
+    /// Returns the file name for the entry.
+    #[inline]
+    pub fn name(&self) -> &CStr {
+        unsafe { CStr::from_bytes_with_nul_unchecked(&self.name[..=self.name_len]) }
+    }
+}
+
+impl From<DirEntry<'_>> for DirEntryOwned {
+    fn from(entry: DirEntry<'_>) -> Self {
+        let name = entry.name().to_bytes();
+        let name_len = core::cmp::min(name.len(), NAME_MAX);
+        let mut buf = [0; NAME_MAX + 1];
+        buf[..name_len].copy_from_slice(&name[..name_len]);
+
+        Self {
+            ino: entry.inode(),
+            file_type: entry.file_type(),
+            name: buf,
+            name_len,
+        }
+    }
+}
+
+impl core::fmt::Debug for DirEntryOwned {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DirEntryOwned")
+            .field("inode", &self.ino)
+            .field("file_type", &self.file_type)
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// The entries of a directory read in the region of a dirent buffer starting
+/// at `base`, so that walkers can keep those of the parent directories before
+/// it.
+pub(crate) struct Cursor {
+    base: usize,
+    offset: usize,
+    end: usize,
+    seek: bool,
+    max_buffer_len: usize,
+}
+
+impl Cursor {
+    /// Creates a cursor reading in the buffer from `base`.
+    #[inline]
+    pub(crate) const fn new(base: usize) -> Self {
+        Self {
+            base,
+            offset: base,
+            end: base,
+            seek: false,
+            max_buffer_len: usize::MAX,
+        }
+    }
+
+    /// Sets the length the dirent buffer may grow to when an entry does not
+    /// fit.
+    #[inline]
+    pub(crate) const fn max_buffer_len(mut self, len: usize) -> Self {
+        self.max_buffer_len = len;
+        self
+    }
+
+    /// Creates a cursor reading in the whole buffer, from the position after
+    /// the last entry read in `dir`.
+    #[inline]
+    pub(crate) const fn resuming(dir: &Dir) -> Self {
+        let mut this = Self::new(0);
+        this.seek = dir.tell != 0;
+        this
+    }
+
+    /// Returns the start of the region of the cursor.
+    #[inline]
+    pub(crate) const fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Returns the end of the entries read by the cursor.
+    #[inline]
+    pub(crate) const fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Forgets the read entries, which will be read again from the directory
+    /// after the last returned one.
+    #[inline]
+    pub(crate) fn invalidate(&mut self) {
+        self.offset = self.base;
+        self.end = self.base;
+        self.seek = true;
+    }
+
+    /// Returns the next entry of `dir`, reading it in `buf` if needed.
+    pub(crate) fn next<'b, B: DirentBuf>(
+        &mut self,
+        dir: &mut Dir,
+        buf: &'b mut B,
+    ) -> Option<Result<DirEntry<'b>, Error>> {
+        let buffered = buf.get(self.offset..self.end).map_or(0, <[u8]>::len);
+        if buffered < DIRENT_HEADER_LEN {
+            if let Err(err) = self.fill(dir, buf) {
+                return Some(Err(err));
+            }
+        }
+
+        let entry = DirEntry::parse(buf.get(self.offset..self.end)?)?;
+        self.offset += entry.len();
+        dir.tell = entry.offset();
+        Some(Ok(entry))
+    }
+
+    fn fill<B: DirentBuf>(&mut self, dir: &mut Dir, buf: &mut B) -> Result<(), Error> {
+        if self.seek {
+            dir.seek()?;
+            self.seek = false;
+        }
+
+        self.base = core::cmp::min(self.base, buf.len());
+        unsafe { buf.set_len(self.base) };
+        loop {
+            let ptr = unsafe { buf.as_mut_ptr().add(self.base) };
+            let len = buf.capacity() - self.base;
+            match unsafe { getdents64(dir.fd, ptr, len) } {
+                Err(Errno::EINVAL) if buf.capacity() >= self.max_buffer_len => {
+                    return Err(Error::new(Operation::Alloc, Errno::ENOMEM))
+                }
+                Err(Errno::EINVAL) => buf
+                    .reserve(core::cmp::min(
+                        core::cmp::max(buf.capacity() * 3 / 2, self.base + MIN_DIRENT_BUF_LEN),
+                        self.max_buffer_len,
+                    ))
+                    .map_err(|err| Error::new(Operation::Alloc, err))?,
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::new(Operation::Getdents64, err)),
+                Ok(len) => {
+                    unsafe { buf.set_len(self.base + len) };
+                    self.offset = self.base;
+                    self.end = self.base + len;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Reads entries of the `fd` directory in the `len` bytes at `ptr`.
+#[cfg(not(test))]
+#[inline(always)]
+unsafe fn getdents64(fd: RawFd, ptr: *mut u8, len: usize) -> Result<usize, Errno> {
+    syscall!(Sysno::getdents64, fd, ptr, len)
+}
+
+// Also serves synthetic directories, which tests read under Miri.
+#[cfg(test)]
+use tests::getdents64;
+
+/// An iterator over a filesystem directory.
+///
+/// It is a lending iterator rather than an [Iterator]: each [DirEntry] borrows
+/// it, as reading the next entries overwrites the dirent buffer.
+pub struct DirIterator<'a, B: DirentBuf> {
+    dir: &'a mut Dir,
+    buf: &'a mut B,
+    cursor: Cursor,
+}
+
+impl<'a, B: DirentBuf> DirIterator<'a, B> {
+    /// Creates a new iterator over directory `dir` using `buf` as a buffer.
+    #[inline]
+    pub fn new(dir: &'a mut Dir, buf: &'a mut B) -> Result<Self, Error> {
+        if dir.tell != 0 {
+            dir.seek()?;
+        }
+
+        Ok(Self {
+            dir,
+            buf,
+            cursor: Cursor::new(0),
+        })
+    }
+
+    /// Sets the length the dirent buffer may grow to when an entry does not
+    /// fit, failing with [Errno::ENOMEM] beyond. Unbounded by default.
+    #[inline]
+    pub fn max_buffer_len(mut self, len: usize) -> Self {
+        self.cursor = self.cursor.max_buffer_len(len);
+        self
+    }
+
+    /// Returns the next entry, or `None` once the whole directory was read.
+    // Not `Iterator::next`: the entry borrows the iterator.
+    #[allow(clippy::should_implement_trait)]
+    #[inline]
+    pub fn next(&mut self) -> Option<Result<DirEntry<'_>, Error>> {
+        self.cursor.next(self.dir, self.buf)
+    }
+}
+
 /// A [DirentBuf] backed by a [u8] array.
 pub struct ArrayBuffer<const N: usize> {
     mem: MaybeUninit<[u8; N]>,
     len: usize,
 }
 
+/// A [DirentBuf] over caller provided memory, as a static arena.
+pub struct SliceBuffer<'a> {
+    mem: &'a mut [MaybeUninit<u8>],
+    len: usize,
+}
+
 /// A [DirentBuf] backed by a [`Vec<u8>`].
 #[cfg(feature = "alloc")]
 pub struct VecBuffer {
     mem: alloc_crate::vec::Vec<u8>,
 }
 
+/// A [DirentBuf] storing up to `N` bytes inline, and moving to the heap
+/// beyond.
+#[cfg(feature = "alloc")]
+pub struct SmallBuffer<const N: usize> {
+    inline: MaybeUninit<[u8; N]>,
+    len: usize,
+    heap: alloc_crate::vec::Vec<u8>,
+}
+
+/// A [DirentBuf] backed by a [`Vec<u8, A>`](allocator_api2::vec::Vec)
+/// allocated with `A`.
+#[cfg(feature = "allocator-api2")]
+pub struct AllocBuffer<A: Allocator = Global> {
+    mem: allocator_api2::vec::Vec<u8, A>,
+}
+
 /// A [DirentBuf] backed by a `malloc`ated [u8] array.
 #[cfg(feature = "c")]
 pub struct CBuffer {
@@ -358,6 +808,28 @@ impl<const N: usize> ArrayBuffer<N> {
     }
 }
 
+impl<const N: usize> Default for ArrayBuffer<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Clone for ArrayBuffer<N> {
+    fn clone(&self) -> Self {
+        let mut buf = Self::new();
+        buf.len = self.len;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                self.mem.as_ptr() as *const u8,
+                buf.mem.as_mut_ptr() as *mut u8,
+                self.len,
+            );
+        }
+        buf
+    }
+}
+
 impl<const N: usize> DirentBuf for ArrayBuffer<N> {
     #[inline]
     fn reset(&mut self) {
@@ -446,16 +918,129 @@ impl<const N: usize> BorrowMut<[u8]> for ArrayBuffer<N> {
     }
 }
 
-#[cfg(feature = "alloc")]
-impl VecBuffer {
-    /// Creates a new instance of [Self].
+impl<'a> SliceBuffer<'a> {
+    /// Creates a new instance of [Self] using `mem`, whose content is
+    /// overwritten.
     #[inline]
-    pub const fn new() -> Self {
-        Self {
-            mem: alloc_crate::vec::Vec::new(),
-        }
+    pub fn new(mem: &'a mut [u8]) -> Self {
+        // SAFETY: only initialized bytes are ever written to `mem`.
+        let mem = unsafe { &mut *(mem as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        Self::from_uninit(mem)
     }
-}
+
+    /// Creates a new instance of [Self] using the uninitialized `mem`.
+    #[inline]
+    pub fn from_uninit(mem: &'a mut [MaybeUninit<u8>]) -> Self {
+        Self { mem, len: 0 }
+    }
+}
+
+impl DirentBuf for SliceBuffer<'_> {
+    #[inline]
+    fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    #[inline]
+    fn reserve(&mut self, size: usize) -> Result<(), Errno> {
+        if size > self.mem.len() {
+            Err(Errno::ENOMEM)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const u8 {
+        self.mem.as_ptr() as *const u8
+    }
+
+    #[inline]
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.mem.as_mut_ptr() as *mut u8
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.mem.len()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    unsafe fn set_len(&mut self, len: usize) {
+        self.len = len;
+    }
+
+    #[inline(always)]
+    fn shrink_to_fit(&mut self) {}
+}
+
+impl Deref for SliceBuffer<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for SliceBuffer<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl AsRef<[u8]> for SliceBuffer<'_> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsMut<[u8]> for SliceBuffer<'_> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+impl Borrow<[u8]> for SliceBuffer<'_> {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl BorrowMut<[u8]> for SliceBuffer<'_> {
+    #[inline]
+    fn borrow_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl VecBuffer {
+    /// Creates a new instance of [Self].
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            mem: alloc_crate::vec::Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Default for VecBuffer {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[cfg(feature = "alloc")]
 impl DirentBuf for VecBuffer {
@@ -466,20 +1051,23 @@ impl DirentBuf for VecBuffer {
 
     #[inline]
     fn reserve(&mut self, size: usize) -> Result<(), Errno> {
-        if let Some(additional) = size.checked_sub(self.len()) {
-            self.mem.reserve_exact(additional);
+        match size.checked_sub(self.len()) {
+            Some(additional) => self
+                .mem
+                .try_reserve_exact(additional)
+                .map_err(|_| Errno::ENOMEM),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     #[inline]
     fn as_ptr(&self) -> *const u8 {
-        self.mem.as_ptr() as *const u8
+        self.mem.as_ptr()
     }
 
     #[inline]
     fn as_mut_ptr(&mut self) -> *mut u8 {
-        self.mem.as_mut_ptr() as *mut u8
+        self.mem.as_mut_ptr()
     }
 
     #[inline]
@@ -553,6 +1141,322 @@ impl BorrowMut<[u8]> for VecBuffer {
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<const N: usize> SmallBuffer<N> {
+    /// Creates a new instance of [Self].
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            inline: MaybeUninit::<[u8; N]>::uninit(),
+            len: 0,
+            heap: alloc_crate::vec::Vec::new(),
+        }
+    }
+
+    /// Returns `true` if the content moved to the heap.
+    #[inline]
+    pub fn spilled(&self) -> bool {
+        self.heap.capacity() != 0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Clone for SmallBuffer<N> {
+    fn clone(&self) -> Self {
+        let mut buf = Self::new();
+        // Short contents stay inline.
+        if self.len() > N {
+            buf.heap = self.heap.clone();
+        } else {
+            buf.len = self.len();
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    self.as_ptr(),
+                    buf.inline.as_mut_ptr() as *mut u8,
+                    self.len(),
+                );
+            }
+        }
+        buf
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Default for SmallBuffer<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> DirentBuf for SmallBuffer<N> {
+    #[inline]
+    fn reset(&mut self) {
+        unsafe { self.set_len(0) };
+    }
+
+    fn reserve(&mut self, size: usize) -> Result<(), Errno> {
+        if self.spilled() {
+            if let Some(additional) = size.checked_sub(self.heap.len()) {
+                self.heap
+                    .try_reserve_exact(additional)
+                    .map_err(|_| Errno::ENOMEM)?;
+            }
+        } else if size > N {
+            let mut heap = alloc_crate::vec::Vec::new();
+            heap.try_reserve_exact(size).map_err(|_| Errno::ENOMEM)?;
+            heap.extend_from_slice(self.as_slice());
+            self.heap = heap;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const u8 {
+        if self.spilled() {
+            self.heap.as_ptr()
+        } else {
+            self.inline.as_ptr() as *const u8
+        }
+    }
+
+    #[inline]
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        if self.spilled() {
+            self.heap.as_mut_ptr()
+        } else {
+            self.inline.as_mut_ptr() as *mut u8
+        }
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        if self.spilled() {
+            self.heap.capacity()
+        } else {
+            N
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        if self.spilled() {
+            self.heap.len()
+        } else {
+            self.len
+        }
+    }
+
+    #[inline]
+    unsafe fn set_len(&mut self, len: usize) {
+        if self.spilled() {
+            self.heap.set_len(len);
+        } else {
+            self.len = len;
+        }
+    }
+
+    /// Moves the content back inline when it fits.
+    fn shrink_to_fit(&mut self) {
+        if !self.spilled() {
+            return;
+        }
+
+        let len = self.heap.len();
+        if len <= N {
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    self.heap.as_ptr(),
+                    self.inline.as_mut_ptr() as *mut u8,
+                    len,
+                );
+            }
+            self.len = len;
+            self.heap = alloc_crate::vec::Vec::new();
+        } else {
+            self.heap.shrink_to_fit();
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Deref for SmallBuffer<N> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> DerefMut for SmallBuffer<N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> AsRef<[u8]> for SmallBuffer<N> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> AsMut<[u8]> for SmallBuffer<N> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Borrow<[u8]> for SmallBuffer<N> {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> BorrowMut<[u8]> for SmallBuffer<N> {
+    #[inline]
+    fn borrow_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl AllocBuffer {
+    /// Creates a new instance of [Self] using the global allocator.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> AllocBuffer<A> {
+    /// Creates a new instance of [Self] using `alloc`.
+    #[inline]
+    pub const fn new_in(alloc: A) -> Self {
+        Self {
+            mem: allocator_api2::vec::Vec::new_in(alloc),
+        }
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl Default for AllocBuffer {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> DirentBuf for AllocBuffer<A> {
+    #[inline]
+    fn reset(&mut self) {
+        unsafe { self.mem.set_len(0) };
+    }
+
+    #[inline]
+    fn reserve(&mut self, size: usize) -> Result<(), Errno> {
+        match size.checked_sub(self.len()) {
+            Some(additional) => self
+                .mem
+                .try_reserve_exact(additional)
+                .map_err(|_| Errno::ENOMEM),
+            None => Ok(()),
+        }
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const u8 {
+        self.mem.as_ptr()
+    }
+
+    #[inline]
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.mem.as_mut_ptr()
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.mem.capacity()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.mem.len()
+    }
+
+    #[inline]
+    unsafe fn set_len(&mut self, len: usize) {
+        self.mem.set_len(len);
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.mem.shrink_to_fit();
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> Deref for AllocBuffer<A> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> DerefMut for AllocBuffer<A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> AsRef<[u8]> for AllocBuffer<A> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> AsMut<[u8]> for AllocBuffer<A> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> Borrow<[u8]> for AllocBuffer<A> {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+impl<A: Allocator> BorrowMut<[u8]> for AllocBuffer<A> {
+    #[inline]
+    fn borrow_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
 #[cfg(feature = "c")]
 impl CBuffer {
     /// Creates a new instance of [Self].
@@ -609,12 +1513,20 @@ impl DirentBuf for CBuffer {
 
     #[inline]
     fn as_ptr(&self) -> *const u8 {
-        self.mem
+        if self.mem.is_null() {
+            core::ptr::NonNull::dangling().as_ptr()
+        } else {
+            self.mem
+        }
     }
 
     #[inline]
     fn as_mut_ptr(&mut self) -> *mut u8 {
-        self.mem
+        if self.mem.is_null() {
+            core::ptr::NonNull::dangling().as_ptr()
+        } else {
+            self.mem
+        }
     }
 
     #[inline]
@@ -691,3 +1603,472 @@ impl BorrowMut<[u8]> for CBuffer {
         self.as_mut_slice()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use core::cell::RefCell;
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        ffi::CString,
+        vec::Vec,
+    };
+
+    std::thread_local! {
+        /// The entries of the synthetic directories of the current test by
+        /// fd, with the position of the next one to read.
+        static SYNTHETIC_DIRS: RefCell<BTreeMap<RawFd, (Vec<u8>, usize)>> =
+            const { RefCell::new(BTreeMap::new()) };
+    }
+
+    /// Returns a directory holding `entries`, as appended by [push_dirent].
+    ///
+    /// Its fd is never closed, as Miri does not run the syscalls.
+    fn synthetic_dir(entries: Vec<u8>) -> ManuallyDrop<Dir> {
+        let fd = SYNTHETIC_DIRS.with_borrow_mut(|dirs| {
+            let fd = -2 - dirs.len() as RawFd;
+            dirs.insert(fd, (entries, 0));
+            fd
+        });
+        ManuallyDrop::new(unsafe { Dir::from_raw_fd(fd) })
+    }
+
+    /// Reads entries like the `getdents64` syscall, from the synthetic
+    /// directory if `fd` is one: as many whole entries as fit in `len`
+    /// bytes, and [Errno::EINVAL] if the next one does not.
+    pub(super) unsafe fn getdents64(fd: RawFd, ptr: *mut u8, len: usize) -> Result<usize, Errno> {
+        let synthetic = SYNTHETIC_DIRS.with_borrow_mut(|dirs| {
+            let (entries, pos) = dirs.get_mut(&fd)?;
+            let mut end = *pos;
+            while end < entries.len() {
+                let reclen = u16::from_ne_bytes([entries[end + 16], entries[end + 17]]);
+                if end + reclen as usize - *pos > len {
+                    break;
+                }
+                end += reclen as usize;
+            }
+            if end == *pos && end < entries.len() {
+                return Some(Err(Errno::EINVAL));
+            }
+            core::ptr::copy_nonoverlapping(entries[*pos..].as_ptr(), ptr, end - *pos);
+            let read = end - *pos;
+            *pos = end;
+            Some(Ok(read))
+        });
+        synthetic.unwrap_or_else(|| syscall!(Sysno::getdents64, fd, ptr, len))
+    }
+
+    /// Appends a `linux_dirent64` record to `buf`.
+    fn push_dirent(buf: &mut Vec<u8>, ino: u64, d_type: u8, name: &[u8]) {
+        let reclen = (DIRENT_HEADER_LEN + name.len() + 1).next_multiple_of(8);
+        let off = (buf.len() + reclen) as u64;
+        buf.extend_from_slice(&ino.to_ne_bytes());
+        buf.extend_from_slice(&off.to_ne_bytes());
+        buf.extend_from_slice(&(reclen as u16).to_ne_bytes());
+        buf.push(d_type);
+        buf.extend_from_slice(name);
+        buf.resize(off as usize, 0);
+    }
+
+    #[test]
+    fn parse_entries() {
+        let mut buf = Vec::new();
+        push_dirent(&mut buf, 42, 2, b"ttyUSB0");
+        push_dirent(&mut buf, 43, 3, b"odd");
+
+        let entry = DirEntry::parse(&buf).unwrap();
+        assert_eq!(entry.inode(), 42);
+        assert_eq!(entry.file_type(), DirentFileType::Character);
+        assert_eq!(entry.name(), c"ttyUSB0");
+
+        let next = DirEntry::parse(&buf[entry.len()..]).unwrap();
+        assert_eq!(next.offset(), buf.len() as u64);
+        assert_eq!(next.file_type(), DirentFileType::Unknown);
+
+        let owned = DirEntryOwned::from(next);
+        drop(buf);
+        assert_eq!(owned.inode(), 43);
+        assert_eq!(owned.name(), c"odd");
+
+        let mut truncated = Vec::new();
+        push_dirent(&mut truncated, 1, 8, b"file");
+        assert!(DirEntry::parse(&truncated[..truncated.len() - 1]).is_none());
+        assert!(DirEntry::parse(&truncated[..DIRENT_HEADER_LEN - 1]).is_none());
+    }
+
+    /// Entries of a synthetic directory holding `names`, with `.` and `..`.
+    fn entries(names: &[&str]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        push_dirent(&mut buf, 1, 4, b".");
+        push_dirent(&mut buf, 2, 4, b"..");
+        for (i, name) in names.iter().enumerate() {
+            push_dirent(&mut buf, 3 + i as u64, 2, name.as_bytes());
+        }
+        buf
+    }
+
+    /// Reads the names of the remaining entries of `it`.
+    fn read_names<B: DirentBuf>(it: &mut DirIterator<'_, B>) -> Vec<std::string::String> {
+        let mut names = Vec::new();
+        while let Some(entry) = it.next() {
+            names.push(entry.unwrap().name().to_str().unwrap().into());
+        }
+        names
+    }
+
+    #[test]
+    fn synthetic_lending_next() {
+        let names = (0..24).map(|i| std::format!("tty{i}")).collect::<Vec<_>>();
+        let names = names.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+        let mut dir = synthetic_dir(entries(&names));
+
+        let owned = {
+            // Small enough to need several reads, each overwriting the
+            // entries returned before.
+            let mut buf = ArrayBuffer::<64>::new();
+            let mut it = dir.iter(&mut buf).unwrap();
+            let first = it.next().unwrap().unwrap();
+            assert_eq!(first.name(), c".");
+            assert_eq!(first.file_type(), DirentFileType::Directory);
+            let mut owned = Vec::new();
+            while let Some(entry) = it.next() {
+                owned.push(DirEntryOwned::from(entry.unwrap()));
+            }
+            assert!(it.next().is_none());
+            owned
+        };
+
+        // The owned entries outlive the buffer.
+        assert_eq!(owned[0].name(), c"..");
+        assert_eq!(owned.len(), names.len() + 1);
+        for (i, (entry, name)) in owned[1..].iter().zip(&names).enumerate() {
+            assert_eq!(entry.name().to_str().unwrap(), *name);
+            assert_eq!(entry.inode(), 3 + i as u64);
+            assert_eq!(entry.file_type(), DirentFileType::Character);
+        }
+        let len = entries(&names).len() as u64;
+        assert_eq!(dir.tell, len);
+    }
+
+    #[test]
+    fn synthetic_buffer_reuse() {
+        let mut full = synthetic_dir(entries(&["a", "b"]));
+        let mut one = synthetic_dir(entries(&["c"]));
+
+        // The entries of `full` left in the buffer after its first one are
+        // not returned for `one`.
+        let mut buf = ArrayBuffer::<1024>::new();
+        assert_eq!(
+            full.iter(&mut buf).unwrap().next().unwrap().unwrap().name(),
+            c"."
+        );
+        assert_eq!(
+            read_names(&mut one.iter(&mut buf).unwrap()),
+            [".", "..", "c"]
+        );
+    }
+
+    #[test]
+    fn synthetic_growth() {
+        let long = "x".repeat(200);
+        let mut dir = synthetic_dir(entries(&[&long]));
+        let mut buf = ArrayBuffer::<64>::new();
+        let mut it = dir.iter(&mut buf).unwrap();
+        assert_eq!(it.next().unwrap().unwrap().name(), c".");
+        assert_eq!(it.next().unwrap().unwrap().name(), c"..");
+        let err = it.next().unwrap().unwrap_err();
+        assert_eq!(err.operation(), Operation::Alloc);
+        assert_eq!(err.errno(), Errno::ENOMEM);
+
+        #[cfg(feature = "alloc")]
+        {
+            let mut dir = synthetic_dir(entries(&[&long]));
+            let mut buf = VecBuffer::new();
+            let names = read_names(&mut dir.iter(&mut buf).unwrap());
+            assert_eq!(names, [".", "..", long.as_str()]);
+
+            // Growing past 128 bytes is needed to hold the long entry.
+            let mut dir = synthetic_dir(entries(&[&long]));
+            let mut buf = VecBuffer::new();
+            let mut it = dir.iter(&mut buf).unwrap().max_buffer_len(128);
+            let err = loop {
+                if let Err(err) = it.next().unwrap() {
+                    break err;
+                }
+            };
+            assert_eq!(err.errno(), Errno::ENOMEM);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn collect_owned_entries() {
+        let dir = std::env::temp_dir().join(std::format!("tty-info-dir-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let names = (0..32)
+            .map(|i| std::format!("entry-{i:02}"))
+            .collect::<BTreeSet<_>>();
+        for name in &names {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let path = CString::new(dir.to_str().unwrap()).unwrap();
+        let mut fd = Dir::open(&path).unwrap();
+        // Small enough to need several `getdents64` calls.
+        let mut buf = ArrayBuffer::<128>::new();
+        let mut entries = Vec::new();
+        let mut it = fd.iter(&mut buf).unwrap();
+        while let Some(entry) = it.next() {
+            entries.push(DirEntryOwned::from(entry.unwrap()));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let found = entries
+            .iter()
+            .map(|entry| entry.name().to_str().unwrap().into())
+            .filter(|name: &std::string::String| !name.starts_with('.'))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(found, names);
+        assert!(entries.iter().any(|entry| entry.name() == c".."));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reused_buffer() {
+        let root = std::env::temp_dir().join(std::format!("tty-info-reuse-{}", std::process::id()));
+        let open = |dir: &str| Dir::open(&CString::new(root.join(dir).to_str().unwrap()).unwrap());
+        for (dir, file) in [("full", "a"), ("full", "b"), ("one", "c")] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(file), b"").unwrap();
+        }
+
+        // Only the first entry of the first directory is read, the others
+        // are left in the buffer and not returned for the second one.
+        let mut buf = ArrayBuffer::<1024>::new();
+        let mut full = open("full").unwrap();
+        assert!(full.iter(&mut buf).unwrap().next().unwrap().is_ok());
+        let mut one = open("one").unwrap();
+        let mut it = one.iter(&mut buf).unwrap();
+        let mut names = BTreeSet::new();
+        while let Some(entry) = it.next() {
+            names.insert(std::string::String::from(
+                entry.unwrap().name().to_str().unwrap(),
+            ));
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            names,
+            [".", "..", "c"].map(std::string::String::from).into()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn device_metadata() {
+        let dev = Dir::open(c"/dev").unwrap();
+        let full = dev.metadata_at(c"null").unwrap();
+        let light = dev.device_metadata_at(c"null").unwrap();
+        assert!(light.is_char());
+        assert_eq!(light.rdev(), full.rdev());
+        // As read on kernels without `statx`.
+        assert_eq!(DeviceMetadata::from(full), light);
+
+        let err = dev.device_metadata_at(c"missing-tty").unwrap_err();
+        assert_eq!(err.operation(), Operation::Statx);
+        assert_eq!(err.errno(), Errno::ENOENT);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn open_resolve() {
+        let root = std::env::temp_dir().join(std::format!("tty-info-open-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::os::unix::fs::symlink("sub", root.join("link")).unwrap();
+        std::os::unix::fs::symlink("..", root.join("escape")).unwrap();
+
+        let path = CString::new(root.to_str().unwrap()).unwrap();
+        let dir = Dir::open(&path).unwrap();
+        let open = |path: &CStr, resolve| Dir::open_at_with(&dir, path, resolve).map(drop);
+        let errno = |path: &CStr, resolve| open(path, resolve).unwrap_err().errno();
+
+        assert!(open(c"link", Resolve::Beneath).is_ok());
+        assert_eq!(errno(c"escape", Resolve::Beneath), Errno::EXDEV);
+        assert_eq!(errno(c"/sub", Resolve::Beneath), Errno::EXDEV);
+        assert!(open(c"/sub", Resolve::InRoot).is_ok());
+        assert!(open(c"escape/sub", Resolve::InRoot).is_ok());
+        assert_eq!(errno(c"link", Resolve::NoSymlinks), Errno::ELOOP);
+        assert!(open(c"escape", Resolve::Any).is_ok());
+
+        // The fallback for kernels without `openat2`.
+        assert!(open_components(&dir, c"./sub/", false).is_ok());
+        assert!(open_components(&dir, c"/sub", true).is_ok());
+        assert_eq!(
+            open_components(&dir, c"/sub", false).unwrap_err(),
+            Errno::EXDEV
+        );
+        assert_eq!(
+            open_components(&dir, c"sub/..", true).unwrap_err(),
+            Errno::EXDEV
+        );
+        assert_eq!(
+            open_components(&dir, c"link", false).unwrap_err(),
+            Errno::ENOTDIR
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// Makes `openat2` fail with [Errno::EPERM] in the calling thread, like
+    /// the seccomp filters of container runtimes unaware of it.
+    fn deny_openat2() {
+        #[repr(C)]
+        struct SockFilter {
+            code: u16,
+            jt: u8,
+            jf: u8,
+            k: u32,
+        }
+        #[repr(C)]
+        struct SockFprog {
+            len: u16,
+            filter: *const SockFilter,
+        }
+        const PR_SET_NO_NEW_PRIVS: u32 = 38;
+        const SECCOMP_SET_MODE_FILTER: u32 = 1;
+        const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+        const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+        const BPF_LD_W_ABS: u16 = 0x20;
+        const BPF_JEQ_K: u16 = 0x15;
+        const BPF_RET_K: u16 = 0x06;
+
+        let filter = [
+            // Loads the syscall number.
+            SockFilter {
+                code: BPF_LD_W_ABS,
+                jt: 0,
+                jf: 0,
+                k: 0,
+            },
+            SockFilter {
+                code: BPF_JEQ_K,
+                jt: 0,
+                jf: 1,
+                k: Sysno::openat2 as u32,
+            },
+            SockFilter {
+                code: BPF_RET_K,
+                jt: 0,
+                jf: 0,
+                k: SECCOMP_RET_ERRNO | Errno::EPERM.into_raw() as u32,
+            },
+            SockFilter {
+                code: BPF_RET_K,
+                jt: 0,
+                jf: 0,
+                k: SECCOMP_RET_ALLOW,
+            },
+        ];
+        let prog = SockFprog {
+            len: filter.len() as u16,
+            filter: filter.as_ptr(),
+        };
+        unsafe {
+            syscall!(Sysno::prctl, PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0).unwrap();
+            syscall!(
+                Sysno::seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                0,
+                &prog as *const SockFprog
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn open_resolve_denied_openat2() {
+        // Clearing `HAS_OPENAT2` changes how the other tests open paths, so
+        // the check runs in a child process only running this test.
+        if std::env::var_os("TTY_INFO_DENY_OPENAT2").is_none() {
+            let test = std::format!(
+                "{}::open_resolve_denied_openat2",
+                module_path!().split_once("::").unwrap().1
+            );
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args([&test, "--exact", "--include-ignored", "--test-threads=1"])
+                .env("TTY_INFO_DENY_OPENAT2", "1")
+                .status()
+                .unwrap();
+            assert!(status.success(), "{test}: {status}");
+            return;
+        }
+
+        let root =
+            std::env::temp_dir().join(std::format!("tty-info-denied-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::os::unix::fs::symlink("sub", root.join("link")).unwrap();
+        let path = CString::new(root.to_str().unwrap()).unwrap();
+        let dir = Dir::open(&path).unwrap();
+
+        deny_openat2();
+        let sub = Dir::open_at_with(&dir, c"sub", Resolve::Beneath).map(drop);
+        let link = Dir::open_at_with(&dir, c"link", Resolve::Beneath).map(drop);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // The fallback opened the paths one component at a time.
+        assert!(sub.is_ok());
+        assert_eq!(link.unwrap_err().errno(), Errno::ENOTDIR);
+        assert!(!HAS_OPENAT2.load(Ordering::Relaxed));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn small_buffer_spills() {
+        let mut buf = SmallBuffer::<8>::new();
+        buf.push_slice(b"/dev").unwrap();
+        assert!(!buf.spilled());
+        assert_eq!(buf.capacity(), 8);
+
+        buf.reserve(16).unwrap();
+        buf.push_slice(b"/pts/12").unwrap();
+        assert!(buf.spilled());
+        assert_eq!(&*buf, b"/dev/pts/12");
+
+        unsafe { buf.set_len(4) };
+        buf.shrink_to_fit();
+        assert!(!buf.spilled());
+        assert_eq!(&*buf, b"/dev");
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn bounded_growth() {
+        let mut buf = VecBuffer::new();
+        assert_eq!(buf.reserve(usize::MAX).unwrap_err(), Errno::ENOMEM);
+
+        let mut dir = Dir::open(c"/").unwrap();
+        let mut entries = dir.iter(&mut buf).unwrap().max_buffer_len(16);
+        let err = entries.next().unwrap().unwrap_err();
+        assert_eq!(err.operation(), Operation::Alloc);
+        assert_eq!(err.errno(), Errno::ENOMEM);
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn alloc_buffer() {
+        let mut buf = AllocBuffer::new_in(Global);
+        assert_eq!(buf.reserve(usize::MAX).unwrap_err(), Errno::ENOMEM);
+
+        let mut dir = Dir::open(c"/").unwrap();
+        let mut entries = dir.iter(&mut buf).unwrap();
+        let mut found = false;
+        while let Some(entry) = entries.next() {
+            found |= entry.unwrap().name() == c"..";
+        }
+        assert!(found);
+    }
+}
@@ -0,0 +1,574 @@
+use core::{fmt, mem::MaybeUninit};
+
+use crate::{CStr, Dir, DirentBuf, Errno, Error, Operation, ProcessHandle, RawFd, TtyInfo};
+use atoi::FromRadix10Checked;
+use linux_stat::CURRENT_DIRECTORY;
+
+use super::{
+    procfs::{check_gone, with_proc_path, File},
+    DirBuf,
+};
+
+/// Default root of systemd runtime files.
+pub const LOGIND_ROOT: &CStr = c"/run/systemd";
+
+const ID_LEN: usize = 64;
+const USER_LEN: usize = 256;
+const TTY_LEN: usize = 32;
+
+/// A string from a logind file, stored inline.
+#[derive(Clone, Copy)]
+struct Value<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Value<N> {
+    const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    fn parse(value: &[u8]) -> Result<Self, Errno> {
+        if value.len() > N {
+            return Err(Errno::ENAMETOOLONG);
+        }
+
+        let mut res = Self::new();
+        res.buf[..value.len()].copy_from_slice(value);
+        res.len = value.len();
+        Ok(res)
+    }
+
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    #[inline]
+    fn get(&self) -> Option<&[u8]> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.as_bytes())
+        }
+    }
+}
+
+impl<const N: usize> fmt::Debug for Value<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.as_bytes().escape_ascii())
+    }
+}
+
+fn parse_u32(value: &[u8]) -> Result<u32, Errno> {
+    match u32::from_radix_10_checked(value) {
+        (Some(n), len) if len != 0 && len == value.len() => Ok(n),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+#[inline]
+fn parse_bool(value: &[u8]) -> bool {
+    value == b"1"
+}
+
+/// Type of a logind session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SessionType {
+    /// No type has been specified.
+    Unspecified,
+    /// Text login on a tty.
+    Tty,
+    /// X11 graphical session.
+    X11,
+    /// Wayland graphical session.
+    Wayland,
+    /// Mir graphical session.
+    Mir,
+    /// Web session.
+    Web,
+    /// Any other type.
+    Other,
+}
+
+impl From<&[u8]> for SessionType {
+    fn from(value: &[u8]) -> Self {
+        match value {
+            b"" | b"unspecified" => Self::Unspecified,
+            b"tty" => Self::Tty,
+            b"x11" => Self::X11,
+            b"wayland" => Self::Wayland,
+            b"mir" => Self::Mir,
+            b"web" => Self::Web,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A logind session, read from `<root>/sessions/<id>`.
+#[derive(Debug, Clone, Copy)]
+pub struct Session {
+    id: Value<ID_LEN>,
+    uid: u32,
+    user: Value<USER_LEN>,
+    seat: Value<ID_LEN>,
+    tty: Value<TTY_LEN>,
+    vtnr: Option<u32>,
+    kind: SessionType,
+    leader: Option<u32>,
+    active: bool,
+    remote: bool,
+}
+
+impl Session {
+    fn parse_at(dirfd: RawFd, path: &CStr, id: &[u8]) -> Result<Self, Error> {
+        let parse_error = |err| Error::with_path(Operation::Parse, path.to_bytes(), err);
+        let mut res = Self {
+            id: Value::parse(id).map_err(parse_error)?,
+            uid: 0,
+            user: Value::new(),
+            seat: Value::new(),
+            tty: Value::new(),
+            vtnr: None,
+            kind: SessionType::Unspecified,
+            leader: None,
+            active: false,
+            remote: false,
+        };
+        let mut uid = None;
+        let mut err = Ok(());
+
+        File::open_at(dirfd, path)?
+            .for_each_pair(b'=', |key, value| {
+                let field = match key {
+                    b"UID" => parse_u32(value).map(|n| uid = Some(n)),
+                    b"USER" => Value::parse(value).map(|v| res.user = v),
+                    b"SEAT" => Value::parse(value).map(|v| res.seat = v),
+                    b"TTY" => Value::parse(value).map(|v| res.tty = v),
+                    b"VTNR" => parse_u32(value).map(|n| res.vtnr = Some(n).filter(|&n| n != 0)),
+                    b"TYPE" => {
+                        res.kind = value.into();
+                        Ok(())
+                    }
+                    b"LEADER" => parse_u32(value).map(|n| res.leader = Some(n).filter(|&n| n != 0)),
+                    b"ACTIVE" => {
+                        res.active = parse_bool(value);
+                        Ok(())
+                    }
+                    b"REMOTE" => {
+                        res.remote = parse_bool(value);
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                };
+                err = field;
+                err.is_ok()
+            })
+            .map_err(|err| err.or_path(path.to_bytes()))?;
+        err.map_err(parse_error)?;
+
+        res.uid = uid.ok_or(Errno::EINVAL).map_err(parse_error)?;
+        Ok(res)
+    }
+
+    /// Returns the session id.
+    #[inline]
+    pub fn id(&self) -> &[u8] {
+        self.id.as_bytes()
+    }
+
+    /// Returns the user id owning the session.
+    #[inline]
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the user name owning the session.
+    #[inline]
+    pub fn user(&self) -> &[u8] {
+        self.user.as_bytes()
+    }
+
+    /// Returns the seat the session is attached to, if any.
+    #[inline]
+    pub fn seat(&self) -> Option<&[u8]> {
+        self.seat.get()
+    }
+
+    /// Returns the tty name, relative to `/dev`, if any.
+    #[inline]
+    pub fn tty(&self) -> Option<&[u8]> {
+        self.tty.get()
+    }
+
+    /// Returns the virtual terminal number, if any.
+    #[inline]
+    pub const fn vtnr(&self) -> Option<u32> {
+        self.vtnr
+    }
+
+    /// Returns the session type.
+    #[inline]
+    pub const fn kind(&self) -> SessionType {
+        self.kind
+    }
+
+    /// Returns the pid of the session leader, if known.
+    #[inline]
+    pub const fn leader(&self) -> Option<u32> {
+        self.leader
+    }
+
+    /// Returns `true` if the session is the active one of its seat.
+    #[inline]
+    pub const fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns `true` if the session is a remote one.
+    #[inline]
+    pub const fn is_remote(&self) -> bool {
+        self.remote
+    }
+}
+
+/// A logind seat, read from `<root>/seats/<id>`.
+#[derive(Debug, Clone, Copy)]
+pub struct Seat {
+    id: Value<ID_LEN>,
+    active_session: Value<ID_LEN>,
+    active_uid: Option<u32>,
+    is_seat0: bool,
+    can_tty: bool,
+    can_graphical: bool,
+}
+
+impl Seat {
+    fn parse_at(dirfd: RawFd, path: &CStr, id: &[u8]) -> Result<Self, Error> {
+        let parse_error = |err| Error::with_path(Operation::Parse, path.to_bytes(), err);
+        let mut res = Self {
+            id: Value::parse(id).map_err(parse_error)?,
+            active_session: Value::new(),
+            active_uid: None,
+            is_seat0: false,
+            can_tty: false,
+            can_graphical: false,
+        };
+        let mut err = Ok(());
+
+        File::open_at(dirfd, path)?
+            .for_each_pair(b'=', |key, value| {
+                let field = match key {
+                    b"ACTIVE" => Value::parse(value).map(|v| res.active_session = v),
+                    b"ACTIVE_UID" => parse_u32(value).map(|n| res.active_uid = Some(n)),
+                    b"IS_SEAT0" => {
+                        res.is_seat0 = parse_bool(value);
+                        Ok(())
+                    }
+                    b"CAN_TTY" => {
+                        res.can_tty = parse_bool(value);
+                        Ok(())
+                    }
+                    b"CAN_GRAPHICAL" => {
+                        res.can_graphical = parse_bool(value);
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                };
+                err = field;
+                err.is_ok()
+            })
+            .map_err(|err| err.or_path(path.to_bytes()))?;
+        err.map_err(parse_error)?;
+
+        Ok(res)
+    }
+
+    /// Returns the seat id.
+    #[inline]
+    pub fn id(&self) -> &[u8] {
+        self.id.as_bytes()
+    }
+
+    /// Returns the id of the active session, if any.
+    #[inline]
+    pub fn active_session(&self) -> Option<&[u8]> {
+        self.active_session.get()
+    }
+
+    /// Returns the user id of the active session, if any.
+    #[inline]
+    pub const fn active_uid(&self) -> Option<u32> {
+        self.active_uid
+    }
+
+    /// Returns `true` for the default seat.
+    #[inline]
+    pub const fn is_seat0(&self) -> bool {
+        self.is_seat0
+    }
+
+    /// Returns `true` if the seat has virtual terminals.
+    #[inline]
+    pub const fn can_tty(&self) -> bool {
+        self.can_tty
+    }
+
+    /// Returns `true` if the seat has a graphical display.
+    #[inline]
+    pub const fn can_graphical(&self) -> bool {
+        self.can_graphical
+    }
+}
+
+/// Access to the session and seat files systemd-logind keeps under
+/// `/run/systemd`, without going through D-Bus.
+pub struct Logind {
+    root: Dir,
+}
+
+impl Logind {
+    /// Opens the logind files rooted at `root`, relative to current working
+    /// directory.
+    #[inline]
+    pub fn open_root(root: &CStr) -> Result<Self, Error> {
+        Ok(Self {
+            root: Dir::open(root)?,
+        })
+    }
+
+    /// Opens the logind files rooted at [LOGIND_ROOT].
+    #[inline]
+    pub fn open() -> Result<Self, Error> {
+        Self::open_root(LOGIND_ROOT)
+    }
+
+    fn with_path<T, F>(dir: &[u8], id: &[u8], f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&CStr) -> Result<T, Error>,
+    {
+        if id.is_empty()
+            || id.len() > ID_LEN
+            || id == b"."
+            || id == b".."
+            || memchr::memchr2(b'/', 0, id).is_some()
+        {
+            return Err(Error::with_path(Operation::Open, id, Errno::EINVAL));
+        }
+
+        let mut buf = MaybeUninit::<[u8; 9 + ID_LEN + 1]>::uninit();
+        let path = unsafe {
+            let ptr = buf.as_mut_ptr().cast::<u8>();
+            core::ptr::copy_nonoverlapping(dir.as_ptr(), ptr, dir.len());
+            core::ptr::copy_nonoverlapping(id.as_ptr(), ptr.add(dir.len()), id.len());
+            *ptr.add(dir.len() + id.len()) = 0;
+            CStr::from_ptr(ptr.cast())
+        };
+
+        f(path)
+    }
+
+    /// Returns the session `id`.
+    pub fn session(&self, id: &[u8]) -> Result<Session, Error> {
+        Self::with_path(b"sessions/", id, |path| {
+            Session::parse_at(self.root.as_raw_fd(), path, id)
+        })
+    }
+
+    /// Returns the seat `id`.
+    pub fn seat(&self, id: &[u8]) -> Result<Seat, Error> {
+        Self::with_path(b"seats/", id, |path| {
+            Seat::parse_at(self.root.as_raw_fd(), path, id)
+        })
+    }
+
+    /// Returns the session the `pid` process belongs to, looked up from its
+    /// `session-<id>.scope` cgroup.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process does not exist or exited while being read.
+    pub fn session_for_process(&self, pid: u32) -> Result<Option<Session>, Error> {
+        let id = with_proc_path(Some(pid), b"cgroup", |path| {
+            let mut id = None;
+            File::open_at(CURRENT_DIRECTORY, path)?
+                .for_each_line(|line| {
+                    id = session_from_cgroup(line).map(Value::<ID_LEN>::parse);
+                    id.is_none()
+                })
+                .map_err(|err| err.or_path(path.to_bytes()))?;
+            id.transpose()
+                .map_err(|err| Error::with_path(Operation::Parse, path.to_bytes(), err))
+        })
+        .map_err(|err| check_gone(pid, err))?;
+
+        match id {
+            Some(id) => self.session(id.as_bytes()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the session the process referred by `handle` belongs to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process exited.
+    #[inline]
+    pub fn session_for_handle(&self, handle: &ProcessHandle) -> Result<Option<Session>, Error> {
+        handle.with_pid(|pid| self.session_for_process(pid))
+    }
+
+    /// Returns the session logged in on `tty`, scanning `<root>/sessions`
+    /// using `dirent_buf` as dirent buffer.
+    #[inline]
+    pub fn session_for_tty_with_buffer<B: DirentBuf, B1: DirentBuf>(
+        &self,
+        tty: &TtyInfo<B>,
+        dirent_buf: &mut B1,
+    ) -> Result<Option<Session>, Error> {
+        self.session_for_line(tty.name().to_bytes(), dirent_buf)
+    }
+
+    fn session_for_line<B: DirentBuf>(
+        &self,
+        line: &[u8],
+        dirent_buf: &mut B,
+    ) -> Result<Option<Session>, Error> {
+        let mut sessions = match Dir::open_at(&self.root, c"sessions") {
+            Err(err) if err.errno() == Errno::ENOENT => return Ok(None),
+            other => other?,
+        };
+        let fd = sessions.as_raw_fd();
+
+        let mut entries = sessions.iter(dirent_buf)?;
+        while let Some(entry) = entries.next() {
+            let entry = entry?;
+            let id = entry.name();
+            // Skips `.`, `..` and the `<id>.ref` fifos.
+            if memchr::memchr(b'.', id.to_bytes()).is_some() {
+                continue;
+            }
+
+            match Session::parse_at(fd, id, id.to_bytes()) {
+                Err(err) if err.errno() == Errno::ENOENT => (),
+                Ok(session) if session.tty() == Some(line) => return Ok(Some(session)),
+                other => _ = other?,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Same as [Self::session_for_tty_with_buffer] but with default buffer.
+    #[inline]
+    pub fn session_for_tty<B: DirentBuf>(
+        &self,
+        tty: &TtyInfo<B>,
+    ) -> Result<Option<Session>, Error> {
+        self.session_for_tty_with_buffer(tty, &mut DirBuf::new())
+    }
+}
+
+/// Extracts the session id from a `/proc/<pid>/cgroup` line.
+fn session_from_cgroup(line: &[u8]) -> Option<&[u8]> {
+    line.rsplit(|&c| c == b'/')
+        .filter_map(|unit| unit.strip_prefix(b"session-"))
+        .filter_map(|unit| unit.strip_suffix(b".scope"))
+        .find(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    fn fixture() -> Logind {
+        let root = std::ffi::CString::new(std::format!(
+            "{}/testdata/logind",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        Logind::open_root(&root).unwrap()
+    }
+
+    #[test]
+    fn parse_session() {
+        let logind = fixture();
+
+        let session = logind.session(b"2").unwrap();
+        assert_eq!(session.id(), b"2");
+        assert_eq!(session.uid(), 1000);
+        assert_eq!(session.user(), b"alice");
+        assert_eq!(session.seat(), Some(&b"seat0"[..]));
+        assert_eq!(session.tty(), Some(&b"tty2"[..]));
+        assert_eq!(session.vtnr(), Some(2));
+        assert_eq!(session.kind(), SessionType::Wayland);
+        assert_eq!(session.leader(), Some(1234));
+        assert!(session.is_active());
+        assert!(!session.is_remote());
+
+        let session = logind.session(b"c1").unwrap();
+        assert_eq!(session.user(), b"bob");
+        assert_eq!(session.seat(), None);
+        assert_eq!(session.tty(), Some(&b"pts/3"[..]));
+        assert_eq!(session.vtnr(), None);
+        assert_eq!(session.kind(), SessionType::Tty);
+        assert!(session.is_remote());
+
+        let err = logind.session(b"3").unwrap_err();
+        assert_eq!(err.operation(), Operation::Open);
+        assert_eq!(err.path(), Some(&b"sessions/3"[..]));
+        assert_eq!(err.errno(), Errno::ENOENT);
+        assert_eq!(
+            logind.session(b"../seats/seat0").unwrap_err().errno(),
+            Errno::EINVAL
+        );
+    }
+
+    #[test]
+    fn parse_seat() {
+        let seat = fixture().seat(b"seat0").unwrap();
+        assert_eq!(seat.id(), b"seat0");
+        assert_eq!(seat.active_session(), Some(&b"2"[..]));
+        assert_eq!(seat.active_uid(), Some(1000));
+        assert!(seat.is_seat0());
+        assert!(seat.can_tty());
+        assert!(seat.can_graphical());
+    }
+
+    #[test]
+    fn session_for_line() {
+        let logind = fixture();
+        let mut buf = crate::ArrayBuffer::<1024>::new();
+
+        let session = logind.session_for_line(b"pts/3", &mut buf).unwrap();
+        assert_eq!(session.unwrap().id(), b"c1");
+        assert!(logind
+            .session_for_line(b"pts/4", &mut buf)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn cgroup_session() {
+        assert_eq!(
+            session_from_cgroup(b"0::/user.slice/user-1000.slice/session-2.scope"),
+            Some(&b"2"[..])
+        );
+        assert_eq!(
+            session_from_cgroup(b"1:name=systemd:/user.slice/user-1000.slice/session-c1.scope"),
+            Some(&b"c1"[..])
+        );
+        assert_eq!(
+            session_from_cgroup(b"0::/user.slice/user-1000.slice/user@1000.service/app.slice"),
+            None
+        );
+        assert_eq!(session_from_cgroup(b"0::/"), None);
+    }
+}
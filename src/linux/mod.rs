@@ -1,19 +1,45 @@
+mod creds;
 mod dir;
+mod logind;
+mod pidfd;
+mod pidns;
 mod pinfo;
+mod procfs;
+mod task;
+mod uevent;
+mod utmp;
+mod walk;
 
+pub use creds::*;
 pub use dir::*;
+pub use logind::*;
+pub use pidfd::*;
+pub use pidns::*;
 pub use pinfo::*;
+pub use procfs::parse_pid;
+pub use task::*;
+pub use uevent::*;
+pub use utmp::*;
+pub use walk::*;
 
 use core::{
     fmt,
+    hash::{Hash, Hasher},
     mem::{ManuallyDrop, MaybeUninit},
+    ops::ControlFlow,
+    time::Duration,
 };
 
-use linux_stat::{fstatat_cstr, StatAtFlags};
+use linux_raw_sys::general::{timespec, CLOCK_REALTIME, S_IWGRP};
+use linux_stat::CURRENT_DIRECTORY;
+use linux_syscalls::{syscall, Sysno};
 
-pub use linux_stat::{CStr, Dev, RawFd};
+pub use linux_stat::{CStr, Dev, Mode, RawFd, Stat, Timestamp};
 pub use linux_syscalls::Errno;
 
+use crate::{Error, Operation};
+use dir::NAME_MAX;
+
 const TTY_MAJOR: u32 = 4;
 const PTS_MAJOR: u32 = 136;
 const TTY_ACM_MAJOR: u32 = 166;
@@ -21,12 +47,25 @@ const TTY_USB_MAJOR: u32 = 188;
 const NR_CONSOLES: u32 = 64;
 const MAX_U32_LENGTH: usize = 10;
 
+#[cfg(feature = "alloc")]
+type OwnedBuf = SmallBuffer<64>;
+#[cfg(not(feature = "alloc"))]
+type OwnedBuf = ArrayBuffer<4096>;
+
+/// A [TtyInfo] owning its path, which is `Send + Sync` on every backend.
+///
+/// Without the `alloc` feature, the path is stored inline and limited to
+/// `PATH_MAX` bytes.
+pub type OwnedTtyInfo = TtyInfo<OwnedBuf>;
+
 /// A structure that contains informations about a tty.
 #[derive(Clone)]
 pub struct TtyInfo<B: DirentBuf = PathBuf> {
     dev: Dev,
     buf: B,
     offset: usize,
+    stat: Stat,
+    root_relative: bool,
 }
 
 impl<B: DirentBuf> TtyInfo<B> {
@@ -47,6 +86,198 @@ impl<B: DirentBuf> TtyInfo<B> {
     pub fn name(&self) -> &CStr {
         unsafe { CStr::from_ptr(self.buf.as_ptr().add(self.offset).cast()) }
     }
+
+    /// Returns the device full path as a [Path](std::path::Path).
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn as_path(&self) -> &std::path::Path {
+        use std::os::unix::ffi::OsStrExt;
+
+        std::ffi::OsStr::from_bytes(self.path().to_bytes()).as_ref()
+    }
+
+    /// Copies the device full path to a [PathBuf](std::path::PathBuf).
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn to_path_buf(&self) -> std::path::PathBuf {
+        self.as_path().to_path_buf()
+    }
+
+    /// Copies the tty to an [OwnedTtyInfo], which does not borrow nor hold
+    /// the buffer type and is `Send + Sync`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Operation::Alloc] error if the path cannot be copied.
+    pub fn into_owned(self) -> Result<OwnedTtyInfo, Error> {
+        let mut buf = OwnedBuf::new();
+        push_path(&mut buf, &[self.path().to_bytes_with_nul()])?;
+        Ok(TtyInfo {
+            dev: self.dev,
+            buf,
+            offset: self.offset,
+            stat: self.stat,
+            root_relative: self.root_relative,
+        })
+    }
+
+    /// Returns `true` if the tty was found through [SearchOptions::root] or
+    /// [Resolve::InRoot]: its path is then relative to that root rather than
+    /// to the current root and directory.
+    #[inline]
+    pub fn is_root_relative(&self) -> bool {
+        self.root_relative
+    }
+
+    /// Returns the device node metadata, as read when the tty was found or
+    /// by the last [Self::refresh_metadata].
+    #[inline]
+    pub fn metadata(&self) -> &Stat {
+        &self.stat
+    }
+
+    /// Returns the user id owning the device node.
+    #[inline]
+    pub fn uid(&self) -> u32 {
+        self.stat.uid()
+    }
+
+    /// Returns the group id owning the device node.
+    #[inline]
+    pub fn gid(&self) -> u32 {
+        self.stat.gid()
+    }
+
+    /// Returns the device node permissions.
+    #[inline]
+    pub fn mode(&self) -> Mode {
+        self.stat.mode()
+    }
+
+    /// Returns the last access time of the device node, updated by terminal
+    /// input.
+    #[inline]
+    pub fn atime(&self) -> Timestamp {
+        self.stat.atime()
+    }
+
+    /// Returns the last modification time of the device node, updated by
+    /// terminal output.
+    #[inline]
+    pub fn mtime(&self) -> Timestamp {
+        self.stat.mtime()
+    }
+
+    /// Returns `true` if the device node is group writable, so the user
+    /// accepts messages from `write(1)` and `wall(1)` (see `mesg(1)`).
+    #[inline]
+    pub fn accepts_messages(&self) -> bool {
+        self.stat.mode().as_u16() as u32 & S_IWGRP != 0
+    }
+
+    /// Returns how much time passed since the last terminal input, as shown
+    /// by `w(1)` and `who -u`.
+    ///
+    /// The access time is the one of [Self::metadata], call
+    /// [Self::refresh_metadata] first to get the current value.
+    pub fn idle_for(&self) -> Result<Duration, Error> {
+        let mut now = MaybeUninit::<timespec>::uninit();
+        loop {
+            match unsafe { syscall!(Sysno::clock_gettime, CLOCK_REALTIME, now.as_mut_ptr()) } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::new(Operation::ClockGettime, err)),
+                Ok(_) => break,
+            }
+        }
+        let now = unsafe { now.assume_init() };
+        let now = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+
+        let atime = self.atime();
+        let atime = Duration::new(atime.secs().max(0) as u64, atime.nsecs());
+
+        Ok(now.saturating_sub(atime))
+    }
+
+    /// Reads again the device node metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Errno::ENOENT] if the path does not refer to the device
+    /// anymore, and [Errno::EXDEV] if it is [root relative](Self::is_root_relative).
+    pub fn refresh_metadata(&mut self) -> Result<(), Error> {
+        if self.root_relative {
+            let path = self.path().to_bytes();
+            return Err(Error::with_path(Operation::Lookup, path, Errno::EXDEV));
+        }
+
+        let dir = ManuallyDrop::new(unsafe { Dir::from_raw_fd(CURRENT_DIRECTORY) });
+        let md = dir.metadata_at(self.path())?;
+        if !md.is_char() || md.rdev() != self.dev {
+            let path = self.path().to_bytes();
+            return Err(Error::with_path(Operation::Lookup, path, Errno::ENOENT));
+        }
+
+        self.stat = md;
+        Ok(())
+    }
+}
+
+/// Serializes the device, as its major and minor numbers, the path and the
+/// name.
+#[cfg(feature = "serde")]
+impl<B: DirentBuf> serde::Serialize for TtyInfo<B> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use crate::serde_dev::{LossyStr, Parts};
+        use serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("TtyInfo", 3)?;
+        s.serialize_field("device", &Parts::from(self.dev))?;
+        s.serialize_field("path", &LossyStr(self.path()))?;
+        s.serialize_field("name", &LossyStr(self.name()))?;
+        s.end()
+    }
+}
+
+/// Displays the path.
+#[cfg(feature = "std")]
+impl<B: DirentBuf> fmt::Display for TtyInfo<B> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_path().display(), f)
+    }
+}
+
+/// Compares the device, then the path.
+impl<B: DirentBuf> PartialEq for TtyInfo<B> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.dev == other.dev && self.path() == other.path()
+    }
+}
+
+impl<B: DirentBuf> Eq for TtyInfo<B> {}
+
+impl<B: DirentBuf> PartialOrd for TtyInfo<B> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by device, then by path.
+impl<B: DirentBuf> Ord for TtyInfo<B> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        (self.dev, self.path()).cmp(&(other.dev, other.path()))
+    }
+}
+
+impl<B: DirentBuf> Hash for TtyInfo<B> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.dev.hash(state);
+        self.path().hash(state);
+    }
 }
 
 impl<B: DirentBuf> fmt::Debug for TtyInfo<B> {
@@ -63,155 +294,308 @@ fn try_path_guessing<B: DirentBuf>(
     dirfd: &Dir,
     file: &CStr,
     ttynr: Dev,
+    resolve: Resolve,
     path: &mut B,
-) -> Result<Option<()>, Errno> {
-    loop {
-        unsafe {
-            match fstatat_cstr(dirfd.as_raw_fd(), file, StatAtFlags::SYMLINK_NOFOLLOW) {
-                Err(Errno::ENOENT) => return Ok(None),
-                Err(Errno::EINTR) => (),
-                Err(err) => return Err(err),
-                Ok(md) => {
-                    return if md.is_char() && md.rdev() == ttynr {
-                        let file = file.to_bytes();
-
-                        path.reserve(path.len() + file.len() + 2)?;
-
-                        path.push_slice(b"/")?;
-                        path.push_slice(file)?;
-                        path.push_slice(b"\0")?;
-
-                        Ok(Some(()))
-                    } else {
-                        Ok(None)
-                    };
+) -> Result<Option<Stat>, Error> {
+    let bytes = file.to_bytes();
+    let md = match memchr::memrchr(b'/', bytes) {
+        None => dirfd.metadata_at(file),
+        // The directories leading to the guess are resolved as the searched
+        // ones, and the guess itself is not followed.
+        Some(slash) => {
+            let mut parent = [0u8; NAME_MAX + 1];
+            if slash > NAME_MAX {
+                return Ok(None);
+            }
+            parent[..slash].copy_from_slice(&bytes[..slash]);
+            let parent = unsafe { CStr::from_bytes_with_nul_unchecked(&parent[..=slash]) };
+            let leaf = unsafe {
+                CStr::from_bytes_with_nul_unchecked(&file.to_bytes_with_nul()[slash + 1..])
+            };
+            match Dir::open_at_with(dirfd, parent, resolve) {
+                Err(err) if matches!(err.errno(), Errno::ENOENT | Errno::ENOTDIR) => {
+                    return Ok(None)
                 }
+                Err(err) => return Err(err.in_dir(path)),
+                Ok(parent) => parent
+                    .metadata_at(leaf)
+                    .map_err(|err| Error::with_path(err.operation(), bytes, err.errno())),
             }
         }
+    };
+    match md {
+        Err(err) if err.errno() == Errno::ENOENT => Ok(None),
+        Err(err) => Err(err.in_dir(path)),
+        Ok(md) if md.is_char() => try_path(md, file, ttynr, path),
+        Ok(_) => Ok(None),
     }
 }
 
-#[inline]
-fn statat(dirfd: &Dir, file: &CStr) -> Result<linux_stat::Stat, Errno> {
-    loop {
-        match unsafe { fstatat_cstr(dirfd.as_raw_fd(), file, StatAtFlags::SYMLINK_NOFOLLOW) } {
-            Err(Errno::EINTR) => (),
-            other => return other,
-        }
+/// Returns the path prefix of the tty found in `dir`, without its trailing
+/// slashes.
+fn dir_prefix(dir: &CStr) -> &[u8] {
+    let mut dir = dir.to_bytes();
+    while let [rest @ .., b'/'] = dir {
+        dir = rest;
     }
+    dir
 }
 
 #[inline(always)]
 fn try_path<B: DirentBuf>(
-    md: linux_stat::Stat,
+    md: Stat,
     file: &CStr,
     ttynr: Dev,
     path: &mut B,
-) -> Result<Option<()>, Errno> {
+) -> Result<Option<Stat>, Error> {
     if md.rdev() == ttynr {
-        let file = file.to_bytes();
-
-        path.reserve(path.len() + file.len() + 2)?;
-
-        path.push_slice(b"/")?;
-        path.push_slice(file)?;
-        path.push_slice(b"\0")?;
-
-        Ok(Some(()))
+        push_path(path, &[b"/", file.to_bytes(), b"\0"])?;
+        Ok(Some(md))
     } else {
         Ok(None)
     }
 }
 
-fn scandir<B1: DirentBuf, B2: DirentBuf>(
-    mut dirfd: Dir,
-    ttynr: Dev,
-    buf: &mut B1,
-    path: &mut B2,
-) -> Result<Option<()>, Errno> {
-    let dupfd = ManuallyDrop::new(unsafe { Dir::from_raw_fd(dirfd.as_raw_fd()) });
-
-    let mut dirit = dirfd.iter(buf)?;
-    while let Some(entry) = dirit.next() {
-        let entry = entry?;
-        let name_cstr = entry.name();
-        let name = name_cstr.to_bytes();
+/// Appends all `parts` to `path`.
+fn push_path<B: DirentBuf>(path: &mut B, parts: &[&[u8]]) -> Result<(), Error> {
+    let len = parts.iter().map(|part| part.len()).sum::<usize>();
+    path.reserve(path.len() + len)
+        .and_then(|_| parts.iter().try_for_each(|part| path.push_slice(part)))
+        .map_err(|err| Error::new(Operation::Alloc, err))
+}
 
-        if name == b"." || name == b".." {
-            continue;
-        }
+#[cfg(feature = "alloc")]
+pub(crate) type DirBuf = VecBuffer;
+#[cfg(all(feature = "c", not(feature = "alloc")))]
+pub(crate) type DirBuf = CBuffer;
+#[cfg(all(not(feature = "c"), not(feature = "alloc")))]
+pub(crate) type DirBuf = ArrayBuffer<2048>;
 
-        let (ft, md) = match entry.file_type().into() {
-            linux_stat::FileType::Unknown => {
-                let md = statat(&dupfd, name_cstr)?;
-                (md.file_type(), Some(md))
-            }
-            ft => (ft, None),
-        };
+#[cfg(feature = "alloc")]
+pub(crate) type PathBuf = SmallBuffer<64>;
+#[cfg(all(feature = "c", not(feature = "alloc")))]
+pub(crate) type PathBuf = CBuffer;
+#[cfg(all(not(feature = "c"), not(feature = "alloc")))]
+pub(crate) type PathBuf = ArrayBuffer<4096>;
 
-        match ft {
-            linux_stat::FileType::Character => {
-                let md = if let Some(md) = md {
-                    md
-                } else {
-                    statat(&dupfd, name_cstr)?
-                };
+/// Where a tty is searched in each of the directories given to the lookups,
+/// after trying the name its device number usually has.
+///
+/// The default matches glibc `ttyname`: the `pts` subdirectory first, then the
+/// top level of the directory, without descending in other subdirectories.
+///
+/// Recursive searches fail with an [Operation::Walk] error on directory loops
+/// and trees deeper than [DEFAULT_DEPTH_LIMIT].
+#[derive(Debug, Clone, Copy)]
+pub struct SearchOptions<'a> {
+    /// Subdirectories searched, in order, before the top level, and not again
+    /// by recursive searches.
+    pub priority: &'a [&'a CStr],
+    /// Whether the other subdirectories are then searched recursively.
+    pub recursive: bool,
+    /// Whether to skip, when searching recursively, the subdirectories on
+    /// another filesystem than the searched directory.
+    pub same_filesystem: bool,
+    /// Subdirectories, relative to the searched directory, skipped when
+    /// searching recursively.
+    pub exclude: &'a [&'a CStr],
+    /// Whether to compare the candidate device nodes with
+    /// [Dir::device_metadata_at], reading the full metadata only for the
+    /// found one.
+    pub device_metadata: bool,
+    /// Directory the directories given to the lookups are relative to,
+    /// instead of the current directory. The paths of the found ttys are
+    /// then relative to it too.
+    pub root: Option<&'a Dir>,
+    /// How the directories given to the lookups, and their subdirectories,
+    /// are opened. Absolute directories need [Resolve::Any] or
+    /// [Resolve::InRoot].
+    pub resolve: Resolve,
+    /// Length the dirent buffer may grow to when an entry does not fit,
+    /// failing with [Errno::ENOMEM] beyond. Unbounded by default.
+    pub max_dirent_buf_len: usize,
+}
 
-                if Some(()) == try_path(md, name_cstr, ttynr, path)? {
-                    return Ok(Some(()));
-                }
-            }
-            linux_stat::FileType::Directory => {
-                _ = dirit;
-                {
-                    let new_dirfd = Dir::open_at(&dupfd, name_cstr)?;
-                    let old_len = path.len();
-                    if Some(()) == scandir(new_dirfd, ttynr, buf, path)? {
-                        return Ok(Some(()));
-                    }
-                    unsafe { path.set_len(old_len) };
-                }
+impl SearchOptions<'static> {
+    /// Subdirectories of `/dev` which do not hold ttys.
+    pub const DEFAULT_EXCLUDE: &'static [&'static CStr] = &[
+        c".udev",
+        c"block",
+        c"bus",
+        c"char",
+        c"cpu",
+        c"disk",
+        c"dri",
+        c"fd",
+        c"hugepages",
+        c"input",
+        c"mapper",
+        c"mqueue",
+        c"net",
+        c"shm",
+        c"snd",
+    ];
 
-                dirit = dirfd.iter(buf)?;
-            }
-            _ => (),
+    /// Creates the default options.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            priority: &[c"pts"],
+            recursive: false,
+            same_filesystem: true,
+            exclude: Self::DEFAULT_EXCLUDE,
+            device_metadata: false,
+            root: None,
+            resolve: Resolve::Any,
+            max_dirent_buf_len: usize::MAX,
         }
     }
+}
 
-    Ok(None)
+impl Default for SearchOptions<'static> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[cfg(feature = "alloc")]
-type DirBuf = VecBuffer;
-#[cfg(all(feature = "c", not(feature = "alloc")))]
-type DirBuf = CBuffer;
-#[cfg(all(not(feature = "c"), not(feature = "alloc")))]
-type DirBuf = ArrayBuffer<2048>;
+impl SearchOptions<'_> {
+    /// Opens one of the directories given to the lookups.
+    fn open(&self, dir: &CStr) -> Result<Dir, Error> {
+        let cwd = ManuallyDrop::new(unsafe { Dir::from_raw_fd(CURRENT_DIRECTORY) });
+        Dir::open_at_with(self.root.unwrap_or(&cwd), dir, self.resolve)
+    }
+}
 
-#[cfg(feature = "alloc")]
-type PathBuf = VecBuffer;
-#[cfg(all(feature = "c", not(feature = "alloc")))]
-type PathBuf = CBuffer;
-#[cfg(all(not(feature = "c"), not(feature = "alloc")))]
-type PathBuf = ArrayBuffer<4096>;
+fn match_tty(
+    entry: &WalkEntry<'_>,
+    ttynr: Dev,
+    options: &SearchOptions<'_>,
+) -> Result<ControlFlow<Stat>, Error> {
+    if entry.file_type() == DirentFileType::Character {
+        if options.device_metadata {
+            let md = entry
+                .dir()
+                .device_metadata_at(entry.name())
+                .map_err(|err| {
+                    Error::with_path(err.operation(), entry.path().to_bytes(), err.errno())
+                })?;
+            if !md.is_char() || md.rdev() != ttynr {
+                return Ok(ControlFlow::Continue(()));
+            }
+        }
+        let md = entry.metadata()?;
+        if md.rdev() == ttynr {
+            return Ok(ControlFlow::Break(md));
+        }
+    }
+    Ok(ControlFlow::Continue(()))
+}
 
-#[inline(always)]
 fn find_in_dir<B1: DirentBuf, B2: DirentBuf>(
     dir: &CStr,
     guessing: &CStr,
     ttynr: Dev,
+    options: &SearchOptions<'_>,
     buf: &mut B1,
     path: &mut B2,
-) -> Result<Option<()>, Errno> {
-    path.push_c_str(dir)?;
+) -> Result<Option<Stat>, Error> {
+    push_path(path, &[dir_prefix(dir)])?;
+    let dirfd = options.open(dir)?;
 
-    let dirfd = Dir::open(dir)?;
+    if let Some(md) = try_path_guessing(&dirfd, guessing, ttynr, options.resolve, path)? {
+        return Ok(Some(md));
+    }
+
+    search_dir(dir, dirfd, options, buf, path, |entry| {
+        match_tty(entry, ttynr, options)
+    })
+}
 
-    if Some(()) == try_path_guessing(&dirfd, guessing, ttynr, path)? {
-        return Ok(Some(()));
+/// Visits the entries of `dirfd`, opened from `dir` and whose path is in
+/// `path`, in the order `options` tells, until `visit` breaks.
+fn search_dir<B1, B2, T, V>(
+    dir: &CStr,
+    mut dirfd: Dir,
+    options: &SearchOptions<'_>,
+    buf: &mut B1,
+    path: &mut B2,
+    mut visit: V,
+) -> Result<Option<T>, Error>
+where
+    B1: DirentBuf,
+    B2: DirentBuf,
+    V: FnMut(&WalkEntry<'_>) -> Result<ControlFlow<T>, Error>,
+{
+    let dir_len = path.len();
+
+    for subdir in options.priority {
+        let mut subdirfd = match Dir::open_at_with(&dirfd, subdir, options.resolve) {
+            Err(err) if matches!(err.errno(), Errno::ENOENT | Errno::ENOTDIR) => continue,
+            Err(err) => return Err(err.in_dir(path)),
+            Ok(subdirfd) => subdirfd,
+        };
+        push_path(path, &[b"/", subdir.to_bytes()])?;
+        let found = Walker::new(buf)
+            .max_depth(0)
+            .max_buffer_len(options.max_dirent_buf_len)
+            .walk(&mut subdirfd, path, &mut visit)?;
+        if found.is_some() {
+            return Ok(found);
+        }
+        unsafe { path.set_len(dir_len) };
     }
 
-    scandir(dirfd, ttynr, buf, path)
+    let found = Walker::new(buf)
+        .max_depth(0)
+        .max_buffer_len(options.max_dirent_buf_len)
+        .walk(&mut dirfd, path, &mut visit)?;
+    if found.is_some() || !options.recursive {
+        return Ok(found);
+    }
+
+    let mut dirfd = options.open(dir)?;
+    Walker::new(buf)
+        .same_filesystem(options.same_filesystem)
+        .resolve(options.resolve)
+        .max_buffer_len(options.max_dirent_buf_len)
+        .prune(|entry| {
+            let name = &entry.path().to_bytes()[dir_len + 1..];
+            // The priority subdirectories were searched already.
+            let searched = options
+                .priority
+                .iter()
+                .any(|subdir| subdir.to_bytes() == name);
+            searched || options.exclude.iter().any(|dir| dir.to_bytes() == name)
+        })
+        .walk(&mut dirfd, path, |entry| {
+            // The top level was searched already.
+            if entry.depth() == 0 {
+                Ok(ControlFlow::Continue(()))
+            } else {
+                visit(entry)
+            }
+        })
+}
+
+/// Returns the metadata of `entry` if it is a tty device node.
+fn tty_metadata(entry: &WalkEntry<'_>, options: &SearchOptions<'_>) -> Result<Option<Stat>, Error> {
+    if entry.file_type() != DirentFileType::Character {
+        return Ok(None);
+    }
+    if options.device_metadata {
+        let md = entry
+            .dir()
+            .device_metadata_at(entry.name())
+            .map_err(|err| {
+                Error::with_path(err.operation(), entry.path().to_bytes(), err.errno())
+            })?;
+        if !md.is_char() || !is_tty_device(md.rdev()) {
+            return Ok(None);
+        }
+    }
+    let md = entry.metadata()?;
+    Ok((md.is_char() && is_tty_device(md.rdev())).then_some(md))
 }
 
 fn concat_cstr_number<const N: usize>(
@@ -231,9 +615,29 @@ fn concat_cstr_number<const N: usize>(
     }
 }
 
+#[cfg(feature = "serde")]
+#[inline]
+pub(crate) fn dev_parts(dev: Dev) -> (u32, u32) {
+    (dev.major(), dev.minor())
+}
+
+#[cfg(feature = "serde")]
+#[inline]
+pub(crate) fn dev_from_parts(major: u32, minor: u32) -> Dev {
+    Dev::from_split(major, minor)
+}
+
+/// Returns `true` if `rdev` has the major number of a tty.
+pub(crate) fn is_tty_device(rdev: Dev) -> bool {
+    matches!(
+        rdev.major(),
+        TTY_MAJOR | PTS_MAJOR | TTY_ACM_MAJOR | TTY_USB_MAJOR
+    )
+}
+
 #[inline(always)]
 pub(crate) fn with_default_paths<'a, T, F: FnOnce([&'a CStr; 1]) -> T>(f: F) -> T {
-    f([unsafe { CStr::from_bytes_with_nul_unchecked(b"/dev\0") }])
+    f([c"/dev"])
 }
 
 impl<B: DirentBuf> TtyInfo<B> {
@@ -242,16 +646,33 @@ impl<B: DirentBuf> TtyInfo<B> {
     ///
     /// # Errors
     ///
-    /// Returns [Errno::ENOTTY] if major device number is not a valid tty and
-    /// [Errno::ENOENT] if it is not present. Other [Errno]s can be returned
-    /// due to `open`, `getdents64`, `lseek` and `fstatat` syscalls
-    /// or memory allocations.
+    /// Returns an [Operation::Lookup] error with [Errno::ENOTTY] if major
+    /// device number is not a valid tty and [Errno::ENOENT] if it is not
+    /// present. Other [Error]s can be returned due to `open`, `getdents64`,
+    /// `lseek` and `fstatat` syscalls or memory allocations.
+    #[inline]
     pub fn by_device_with_buffers_in<'a, I, B1>(
         rdev: Dev,
         dirs: I,
         dirent_buf: &mut B1,
-        mut path_buf: B,
-    ) -> Result<Self, Errno>
+        path_buf: B,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+        B1: DirentBuf,
+    {
+        Self::search_with_buffers_in(rdev, dirs, &SearchOptions::new(), dirent_buf, path_buf)
+    }
+
+    /// Same as [Self::by_device_with_buffers_in] but searching `dirs` as
+    /// `options` tells.
+    pub fn search_with_buffers_in<'a, I, B1>(
+        rdev: Dev,
+        dirs: I,
+        options: &SearchOptions<'_>,
+        dirent_buf: &mut B1,
+        path_buf: B,
+    ) -> Result<Self, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
         B1: DirentBuf,
@@ -275,23 +696,95 @@ impl<B: DirentBuf> TtyInfo<B> {
             TTY_USB_MAJOR => {
                 concat_cstr_number(&mut guess_buf, b"ttyUSB", rdev.minor());
             }
-            _ => return Err(Errno::ENOTTY),
+            _ => return Err(Error::new(Operation::Lookup, Errno::ENOTTY)),
         }
         let guess_buf = unsafe { guess_buf.assume_init() };
         let guessing = unsafe { CStr::from_ptr(guess_buf.as_slice().as_ptr().cast()) };
 
+        Self::find_with_buffers_in(rdev, guessing, dirs, options, dirent_buf, path_buf)
+    }
+
+    /// Find a tty by its device number in `dirs`, trying `guessing` before
+    /// searching each of them as `options` tells.
+    pub(crate) fn find_with_buffers_in<'a, I, B1>(
+        rdev: Dev,
+        guessing: &CStr,
+        dirs: I,
+        options: &SearchOptions<'_>,
+        dirent_buf: &mut B1,
+        mut path_buf: B,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+        B1: DirentBuf,
+    {
         for dir in dirs {
-            if Some(()) == find_in_dir(dir, guessing, rdev, dirent_buf, &mut path_buf)? {
+            path_buf.reset();
+            if let Some(md) = find_in_dir(dir, guessing, rdev, options, dirent_buf, &mut path_buf)?
+            {
                 path_buf.shrink_to_fit();
                 return Ok(TtyInfo {
                     dev: rdev,
                     buf: path_buf,
-                    offset: dir.to_bytes().len() + 1,
+                    offset: dir_prefix(dir).len() + 1,
+                    stat: md,
+                    root_relative: options.root.is_some() || options.resolve == Resolve::InRoot,
                 });
             }
         }
 
-        Err(Errno::ENOENT)
+        Err(Error::new(Operation::Lookup, Errno::ENOENT))
+    }
+
+    /// Calls `visit` with each tty found in `dirs`, searched as `options`
+    /// tells, using `dirent_buf` as dirent buffer, until `visit` breaks.
+    ///
+    /// The ttys are visited in search order, with their own path buffer. A
+    /// tty with several device nodes is visited for each of them.
+    ///
+    /// # Errors
+    ///
+    /// [Error]s can be returned due to `open`, `getdents64`, `lseek` and
+    /// `fstatat` syscalls or memory allocations.
+    pub fn enumerate_with_buffers_in<'a, I, B1, T, F>(
+        dirs: I,
+        options: &SearchOptions<'_>,
+        dirent_buf: &mut B1,
+        mut visit: F,
+    ) -> Result<Option<T>, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+        B1: DirentBuf,
+        B: Default,
+        F: FnMut(Self) -> ControlFlow<T>,
+    {
+        let mut path_buf = B::default();
+        for dir in dirs {
+            path_buf.reset();
+            push_path(&mut path_buf, &[dir_prefix(dir)])?;
+            let offset = path_buf.len() + 1;
+            let dirfd = options.open(dir)?;
+            let found = search_dir(dir, dirfd, options, dirent_buf, &mut path_buf, |entry| {
+                let md = match tty_metadata(entry, options)? {
+                    Some(md) => md,
+                    None => return Ok(ControlFlow::Continue(())),
+                };
+                let mut buf = B::default();
+                push_path(&mut buf, &[entry.path().to_bytes_with_nul()])?;
+                Ok(visit(TtyInfo {
+                    dev: md.rdev(),
+                    buf,
+                    offset,
+                    stat: md,
+                    root_relative: options.root.is_some() || options.resolve == Resolve::InRoot,
+                }))
+            })?;
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+
+        Ok(None)
     }
 
     /// Shortcut for [RawProcessInfo::current] + [Self::by_device_with_buffers_in].
@@ -300,7 +793,7 @@ impl<B: DirentBuf> TtyInfo<B> {
         dirs: I,
         dirent_buf: &mut B1,
         path_buf: B,
-    ) -> Result<Option<Self>, Errno>
+    ) -> Result<Option<Self>, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
         B1: DirentBuf,
@@ -318,7 +811,7 @@ impl<B: DirentBuf> TtyInfo<B> {
         dirs: I,
         dirent_buf: &mut B1,
         path_buf: B,
-    ) -> Result<Option<Self>, Errno>
+    ) -> Result<Option<Self>, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
         B1: DirentBuf,
@@ -335,7 +828,7 @@ impl<B: DirentBuf> TtyInfo<B> {
         rdev: Dev,
         buf: &mut B1,
         path: B,
-    ) -> Result<Self, Errno> {
+    ) -> Result<Self, Error> {
         with_default_paths(|dirs| Self::by_device_with_buffers_in(rdev, dirs, buf, path))
     }
 
@@ -344,7 +837,7 @@ impl<B: DirentBuf> TtyInfo<B> {
     pub fn current_with_buffers<B1: DirentBuf>(
         dirent_buf: &mut B1,
         path_buf: B,
-    ) -> Result<Option<Self>, Errno> {
+    ) -> Result<Option<Self>, Error> {
         RawProcessInfo::current()?
             .tty
             .map(|rdev| Self::by_device_with_buffers(rdev, dirent_buf, path_buf))
@@ -357,7 +850,7 @@ impl<B: DirentBuf> TtyInfo<B> {
         pid: u32,
         dirent_buf: &mut B1,
         path_buf: B,
-    ) -> Result<Option<Self>, Errno> {
+    ) -> Result<Option<Self>, Error> {
         RawProcessInfo::for_process(pid)?
             .tty
             .map(|rdev| Self::by_device_with_buffers(rdev, dirent_buf, path_buf))
@@ -366,18 +859,55 @@ impl<B: DirentBuf> TtyInfo<B> {
 }
 
 impl TtyInfo<PathBuf> {
+    /// Makes the tty at `name`, relative to `dir`, from its metadata.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn at(dir: &CStr, name: &[u8], md: Stat) -> Result<Self, Error> {
+        let mut buf = PathBuf::new();
+        push_path(&mut buf, &[dir.to_bytes(), b"/", name, b"\0"])?;
+        Ok(Self {
+            dev: md.rdev(),
+            buf,
+            offset: dir.to_bytes().len() + 1,
+            stat: md,
+            root_relative: false,
+        })
+    }
+
     /// Same as [Self::by_device_with_buffers_in] but with default buffers.
     #[inline]
-    pub fn by_device_in<'a, I>(rdev: Dev, dirs: I) -> Result<Self, Errno>
+    pub fn by_device_in<'a, I>(rdev: Dev, dirs: I) -> Result<Self, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
     {
         Self::by_device_with_buffers_in(rdev, dirs, &mut DirBuf::new(), PathBuf::new())
     }
 
+    /// Same as [Self::search_with_buffers_in] but with default buffers.
+    #[inline]
+    pub fn search_in<'a, I>(rdev: Dev, dirs: I, options: &SearchOptions<'_>) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+    {
+        Self::search_with_buffers_in(rdev, dirs, options, &mut DirBuf::new(), PathBuf::new())
+    }
+
+    /// Same as [Self::enumerate_with_buffers_in] but with default buffers.
+    #[inline]
+    pub fn enumerate_in<'a, I, T, F>(
+        dirs: I,
+        options: &SearchOptions<'_>,
+        visit: F,
+    ) -> Result<Option<T>, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+        F: FnMut(Self) -> ControlFlow<T>,
+    {
+        Self::enumerate_with_buffers_in(dirs, options, &mut DirBuf::new(), visit)
+    }
+
     /// Shortcut for [RawProcessInfo::current] + [Self::by_device_in].
     #[inline]
-    pub fn current_in<'a, I>(dirs: I) -> Result<Option<Self>, Errno>
+    pub fn current_in<'a, I>(dirs: I) -> Result<Option<Self>, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
     {
@@ -389,7 +919,7 @@ impl TtyInfo<PathBuf> {
 
     /// Shortcut for [RawProcessInfo::for_process] + [Self::by_device_in].
     #[inline]
-    pub fn for_process_in<'a, I>(pid: u32, dirs: I) -> Result<Option<Self>, Errno>
+    pub fn for_process_in<'a, I>(pid: u32, dirs: I) -> Result<Option<Self>, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
     {
@@ -402,13 +932,22 @@ impl TtyInfo<PathBuf> {
     /// Same as [Self::by_device_with_buffers_in] but
     /// with default buffers and dirs.
     #[inline]
-    pub fn by_device(rdev: Dev) -> Result<Self, Errno> {
+    pub fn by_device(rdev: Dev) -> Result<Self, Error> {
         Self::by_device_with_buffers(rdev, &mut DirBuf::new(), PathBuf::new())
     }
 
+    /// Same as [Self::enumerate_in] but with default dirs and options.
+    #[inline]
+    pub fn enumerate<T, F>(visit: F) -> Result<Option<T>, Error>
+    where
+        F: FnMut(Self) -> ControlFlow<T>,
+    {
+        with_default_paths(|dirs| Self::enumerate_in(dirs, &SearchOptions::new(), visit))
+    }
+
     /// Shortcut for [RawProcessInfo::current] + [Self::by_device].
     #[inline]
-    pub fn current() -> Result<Option<Self>, Errno> {
+    pub fn current() -> Result<Option<Self>, Error> {
         RawProcessInfo::current()?
             .tty
             .map(Self::by_device)
@@ -417,10 +956,466 @@ impl TtyInfo<PathBuf> {
 
     /// Shortcut for [RawProcessInfo::for_process] + [Self::by_device].
     #[inline]
-    pub fn for_process(pid: u32) -> Result<Option<Self>, Errno> {
+    pub fn for_process(pid: u32) -> Result<Option<Self>, Error> {
         RawProcessInfo::for_process(pid)?
             .tty
             .map(Self::by_device)
             .transpose()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use linux_raw_sys::ioctl::{TIOCGPTN, TIOCSPTLCK};
+    use linux_stat::{fstatat_cstr, StatAtFlags};
+
+    extern crate std;
+    use std::{fs::File, os::fd::AsRawFd};
+
+    /// Opens a pseudo terminal, returning the master side and the device of
+    /// the slave side.
+    pub(crate) fn open_pty() -> Option<(File, Dev)> {
+        let master = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/ptmx")
+            .ok()?;
+        let unlock = 0i32;
+        let mut n = 0u32;
+        unsafe {
+            syscall!(
+                Sysno::ioctl,
+                master.as_raw_fd(),
+                TIOCSPTLCK,
+                &unlock as *const i32
+            )
+            .ok()?;
+            syscall!(
+                Sysno::ioctl,
+                master.as_raw_fd(),
+                TIOCGPTN,
+                &mut n as *mut u32
+            )
+            .ok()?;
+        }
+        let path = std::ffi::CString::new(std::format!("/dev/pts/{n}")).unwrap();
+        let md = unsafe { fstatat_cstr(CURRENT_DIRECTORY, &path, StatAtFlags::empty()) }.ok()?;
+        Some((master, md.rdev()))
+    }
+
+    #[test]
+    fn pty_metadata() {
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+
+        let tty = TtyInfo::by_device(rdev).unwrap();
+        assert_eq!(tty.device(), rdev);
+        #[cfg(feature = "alloc")]
+        assert!(!tty.buf.spilled());
+        assert!(tty.metadata().is_char());
+        assert_eq!(tty.metadata().rdev(), rdev);
+        assert!(tty.idle_for().unwrap() < Duration::from_secs(60));
+
+        // As `mesg y` and `mesg n`, the pty being owned by the caller.
+        let path = std::string::String::from(tty.path().to_str().unwrap());
+        let chmod = |mode| {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        chmod(0o620);
+        let mut tty = TtyInfo::by_device(rdev).unwrap();
+        assert!(tty.accepts_messages());
+        chmod(0o600);
+        assert!(tty.accepts_messages());
+        tty.refresh_metadata().unwrap();
+        assert!(!tty.accepts_messages());
+    }
+
+    #[test]
+    fn owned_pty() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+
+        let tty = TtyInfo::by_device(rdev).unwrap();
+        let owned = TtyInfo::by_device(rdev).unwrap().into_owned().unwrap();
+        assert_send_sync(&owned);
+        assert_eq!(owned.device(), rdev);
+        assert_eq!(owned.path(), tty.path());
+        assert_eq!(owned.name(), tty.name());
+        #[cfg(feature = "std")]
+        {
+            use std::string::ToString;
+
+            assert_eq!(owned.to_path_buf(), tty.as_path());
+            assert_eq!(owned.to_string(), tty.path().to_str().unwrap());
+        }
+        assert!(owned == owned.clone());
+
+        let other = TtyInfo::by_device(rdev).unwrap().into_owned().unwrap();
+        let set = std::collections::HashSet::from([owned.clone(), other]);
+        assert_eq!(set.len(), 1);
+        assert!(set.contains(&owned));
+    }
+
+    #[test]
+    fn rooted_guess() {
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+        let root =
+            std::env::temp_dir().join(std::format!("tty-info-rooted-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir(&root).unwrap();
+        // Points out of the root unless resolved in it.
+        std::os::unix::fs::symlink("/dev/pts", root.join("pts")).unwrap();
+
+        let rootfd = Dir::open(&std::ffi::CString::new(root.to_str().unwrap()).unwrap()).unwrap();
+        let search = |resolve| {
+            let options = SearchOptions {
+                root: Some(&rootfd),
+                resolve,
+                ..SearchOptions::new()
+            };
+            TtyInfo::search_in(rdev, [c"."], &options)
+        };
+        assert!(search(Resolve::Any).is_ok());
+        assert_eq!(search(Resolve::InRoot).unwrap_err().errno(), Errno::ENOENT);
+        assert_eq!(search(Resolve::Beneath).unwrap_err().errno(), Errno::EXDEV);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn slice_buffers() {
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+
+        let mut dirent_mem = [MaybeUninit::uninit(); 1024];
+        let mut path_mem = [0u8; 64];
+        let tty = TtyInfo::by_device_with_buffers(
+            rdev,
+            &mut SliceBuffer::from_uninit(&mut dirent_mem),
+            SliceBuffer::new(&mut path_mem),
+        )
+        .unwrap();
+        assert!(tty.path().to_bytes().starts_with(b"/dev/pts/"));
+
+        let mut path_mem = [0u8; 4];
+        let err = TtyInfo::by_device_with_buffers(
+            rdev,
+            &mut SliceBuffer::from_uninit(&mut dirent_mem),
+            SliceBuffer::new(&mut path_mem),
+        )
+        .unwrap_err();
+        assert_eq!(err.operation(), Operation::Alloc);
+        assert_eq!(err.errno(), Errno::ENOMEM);
+    }
+
+    #[test]
+    fn missing_pty_lookup() {
+        // Scans all of `/dev`, including its subdirectories.
+        let options = SearchOptions {
+            recursive: true,
+            ..SearchOptions::new()
+        };
+        let err = TtyInfo::search_in(Dev::from_split(PTS_MAJOR, 0xfffff), [c"/dev"], &options)
+            .unwrap_err();
+        assert_eq!(err.operation(), Operation::Lookup);
+        assert_eq!(err.errno(), Errno::ENOENT);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn bounded_lookup_buffer() {
+        let options = SearchOptions {
+            max_dirent_buf_len: 16,
+            ..SearchOptions::new()
+        };
+        let err = TtyInfo::search_with_buffers_in(
+            Dev::from_split(PTS_MAJOR, 0xfffff),
+            [c"/dev"],
+            &options,
+            &mut VecBuffer::new(),
+            PathBuf::new(),
+        )
+        .unwrap_err();
+        assert_eq!(err.operation(), Operation::Alloc);
+        assert_eq!(err.errno(), Errno::ENOMEM);
+    }
+
+    /// Names of the entries of `/` but `dev`, to only walk `/dev` when
+    /// searching `/` recursively.
+    fn root_entries_but_dev() -> std::vec::Vec<std::ffi::CString> {
+        use std::os::unix::ffi::OsStringExt;
+
+        std::fs::read_dir("/")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name != "dev")
+            .map(|name| std::ffi::CString::new(name.into_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "creates device nodes, which needs CAP_MKNOD"]
+    fn recursive_full_path() {
+        let (_master, rdev) = open_pty().unwrap();
+        let root = std::env::temp_dir().join(std::format!("tty-info-deep-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&root);
+        let path = root.join("nested/deep/tty");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let status = std::process::Command::new("mknod")
+            .arg(&path)
+            .args(["c", &rdev.major().to_string(), &rdev.minor().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success(), "mknod {}: {status}", path.display());
+
+        let dir = std::ffi::CString::new(root.to_str().unwrap()).unwrap();
+        let options = SearchOptions {
+            recursive: true,
+            ..SearchOptions::new()
+        };
+        let tty = TtyInfo::search_in(rdev, [dir.as_c_str()], &options).unwrap();
+        assert_eq!(tty.path().to_str().unwrap(), path.to_str().unwrap());
+        assert_eq!(tty.name().to_str().unwrap(), "nested/deep/tty");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn multiple_dirs() {
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+        let empty = std::env::temp_dir().join(std::format!("tty-info-dirs-{}", std::process::id()));
+        std::fs::create_dir_all(&empty).unwrap();
+        let empty_dir = std::ffi::CString::new(empty.to_str().unwrap()).unwrap();
+
+        // The prefix of the directory searched first is not kept.
+        let tty = TtyInfo::by_device_in(rdev, [empty_dir.as_c_str(), c"/dev"]);
+        std::fs::remove_dir_all(&empty).unwrap();
+        let tty = tty.unwrap();
+        let path = std::format!("/dev/pts/{}", rdev.minor());
+        assert_eq!(tty.path().to_str().unwrap(), path);
+        assert_eq!(tty.name().to_str().unwrap(), &path[5..]);
+    }
+
+    #[test]
+    fn enumerate_ptys() {
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+
+        let mut ttys = std::vec::Vec::new();
+        let none = TtyInfo::enumerate(|tty| {
+            ttys.push(tty);
+            ControlFlow::<()>::Continue(())
+        });
+        assert!(none.unwrap().is_none());
+        let pty = ttys.iter().find(|tty| tty.device() == rdev).unwrap();
+        let path = std::format!("/dev/pts/{}", rdev.minor());
+        assert_eq!(pty.path().to_str().unwrap(), path);
+        assert_eq!(pty.name().to_str().unwrap(), &path[5..]);
+        assert!(ttys.iter().all(|tty| is_tty_device(tty.device())));
+
+        // Breaking stops the enumeration, with the value of the break.
+        let found = TtyInfo::enumerate(|tty| {
+            if tty.device() == rdev {
+                ControlFlow::Break(tty)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(found.unwrap().unwrap().path(), pty.path());
+
+        // Recursive enumerations visit the subdirectories, but not twice the
+        // priority ones.
+        let exclude = root_entries_but_dev();
+        let exclude = exclude
+            .iter()
+            .map(|name| name.as_c_str())
+            .collect::<std::vec::Vec<_>>();
+        let options = SearchOptions {
+            priority: &[c"dev/pts"],
+            recursive: true,
+            same_filesystem: false,
+            exclude: &exclude,
+            ..SearchOptions::new()
+        };
+        let mut paths = std::vec::Vec::new();
+        TtyInfo::enumerate_in([c"/"], &options, |tty| {
+            if tty.device() == rdev {
+                paths.push(std::string::String::from(tty.path().to_str().unwrap()));
+            }
+            ControlFlow::<()>::Continue(())
+        })
+        .unwrap();
+        assert_eq!(paths, [path]);
+    }
+
+    #[test]
+    fn search_order() {
+        use std::{ffi::CString, string::ToString};
+
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+
+        // A priority subdirectory linking to `/dev/pts`, as in containers
+        // sharing the host ptys.
+        let root =
+            std::env::temp_dir().join(std::format!("tty-info-search-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::os::unix::fs::symlink("/dev/pts", root.join("first")).unwrap();
+        let dir = CString::new(root.to_str().unwrap()).unwrap();
+        let search = |options: &SearchOptions<'_>| {
+            TtyInfo::search_in(rdev, [dir.as_c_str()], options)
+                .map(|tty| tty.name().to_str().unwrap().to_string())
+        };
+        let name = std::format!("first/{}", rdev.minor());
+
+        assert_eq!(
+            search(&SearchOptions::new()).unwrap_err().errno(),
+            Errno::ENOENT
+        );
+        let priority = SearchOptions {
+            priority: &[c"missing", c"first"],
+            ..SearchOptions::new()
+        };
+        assert_eq!(search(&priority).unwrap(), name);
+
+        // The full metadata is still read for the found device.
+        let device_metadata = SearchOptions {
+            device_metadata: true,
+            ..priority
+        };
+        let tty = TtyInfo::search_in(rdev, [dir.as_c_str()], &device_metadata).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(tty.name().to_str().unwrap(), name);
+        let path = std::format!("/dev/pts/{}", rdev.minor());
+        let ino = std::os::unix::fs::MetadataExt::ino(&std::fs::metadata(&path).unwrap());
+        assert_eq!(tty.metadata().inode(), ino);
+
+        // Excluded subdirectories are not walked: only `dev/pts` holds the
+        // pty below `/`.
+        let mut exclude = root_entries_but_dev();
+        exclude.push(CString::new("dev/pts").unwrap());
+        let exclude = exclude
+            .iter()
+            .map(|name| name.as_c_str())
+            .collect::<std::vec::Vec<_>>();
+        let excluded = SearchOptions {
+            priority: &[],
+            recursive: true,
+            same_filesystem: false,
+            exclude: &exclude,
+            ..SearchOptions::new()
+        };
+        let err = TtyInfo::search_in(rdev, [c"/"], &excluded).unwrap_err();
+        assert_eq!(err.errno(), Errno::ENOENT);
+
+        // Lookups anchored to a root directory, as a container one.
+        let rootfd = Dir::open(c"/").unwrap();
+        let in_root = SearchOptions {
+            root: Some(&rootfd),
+            resolve: Resolve::InRoot,
+            ..SearchOptions::new()
+        };
+        let mut tty = TtyInfo::search_in(rdev, [c"/dev"], &in_root).unwrap();
+        assert_eq!(tty.path().to_str().unwrap(), path);
+        assert!(tty.is_root_relative());
+        assert_eq!(tty.refresh_metadata().unwrap_err().errno(), Errno::EXDEV);
+        let beneath = SearchOptions {
+            resolve: Resolve::Beneath,
+            ..in_root
+        };
+        let err = TtyInfo::search_in(rdev, [c"/dev"], &beneath).unwrap_err();
+        assert_eq!(err.errno(), Errno::EXDEV);
+    }
+
+    #[test]
+    #[ignore = "creates device nodes, which needs CAP_MKNOD"]
+    fn search_order_top_level() {
+        use std::string::ToString;
+
+        let (_master, rdev) = open_pty().unwrap();
+        let root = std::env::temp_dir().join(std::format!("tty-info-top-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&root);
+        let mknod = |name: &str| {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let status = std::process::Command::new("mknod")
+                .arg(&path)
+                .args(["c", &rdev.major().to_string(), &rdev.minor().to_string()])
+                .status()
+                .unwrap();
+            assert!(status.success(), "mknod {}: {status}", path.display());
+        };
+        let dir = std::ffi::CString::new(root.to_str().unwrap()).unwrap();
+        let search = |options: &SearchOptions<'_>| {
+            TtyInfo::search_in(rdev, [dir.as_c_str()], options)
+                .map(|tty| tty.name().to_str().unwrap().to_string())
+        };
+
+        mknod("nested/deep/tty");
+        let recursive = SearchOptions {
+            recursive: true,
+            ..SearchOptions::new()
+        };
+        assert_eq!(
+            search(&SearchOptions::new()).unwrap_err().errno(),
+            Errno::ENOENT
+        );
+        assert_eq!(search(&recursive).unwrap(), "nested/deep/tty");
+        let excluded = SearchOptions {
+            exclude: &[c"nested/deep"],
+            ..recursive
+        };
+        assert_eq!(search(&excluded).unwrap_err().errno(), Errno::ENOENT);
+
+        // The top level comes before the other subdirectories, but after
+        // the priority ones.
+        mknod("top");
+        assert_eq!(search(&recursive).unwrap(), "top");
+        mknod("first/tty");
+        let priority = SearchOptions {
+            priority: &[c"missing", c"first"],
+            ..recursive
+        };
+        assert_eq!(search(&priority).unwrap(), "first/tty");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_pty() {
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+
+        let tty = TtyInfo::by_device(rdev).unwrap();
+        let json = serde_json::to_value(&tty).unwrap();
+        assert_eq!(json["device"]["major"], PTS_MAJOR);
+        assert_eq!(json["device"]["minor"], rdev.minor());
+        assert_eq!(json["path"], tty.path().to_str().unwrap());
+        assert_eq!(json["name"], tty.name().to_str().unwrap());
+    }
+}
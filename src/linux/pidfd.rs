@@ -0,0 +1,123 @@
+use crate::{Errno, Error, Operation, RawFd};
+
+use linux_syscalls::{syscall, Sysno};
+
+/// A handle to a process backed by a pidfd.
+///
+/// Unlike a bare pid, a pidfd always refers to the process it was opened for,
+/// even after the process exits and its pid is reused, so it can be used to
+/// check that informations read through `/proc/<pid>` belong to the right
+/// process.
+///
+/// Handles are automatically closed when they go out of scope.
+pub struct ProcessHandle {
+    fd: RawFd,
+    pid: u32,
+}
+
+impl ProcessHandle {
+    /// Opens a handle for the `pid` process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process does not exist and [Errno::ENOSYS] on kernels older than 5.3.
+    pub fn open(pid: u32) -> Result<Self, Error> {
+        loop {
+            match unsafe { syscall!([ro] Sysno::pidfd_open, pid, 0) } {
+                Err(Errno::EINTR) => (),
+                Err(Errno::ESRCH) => {
+                    return Err(Error::new(Operation::PidfdOpen, Errno::ESRCH).process_gone())
+                }
+                Err(err) => return Err(Error::new(Operation::PidfdOpen, err)),
+                Ok(fd) => {
+                    return Ok(Self {
+                        fd: fd as RawFd,
+                        pid,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Opens a handle for the current process.
+    #[inline]
+    pub fn current() -> Result<Self, Error> {
+        let pid = unsafe { syscall!([ro] Sysno::getpid) }
+            .map_err(|err| Error::new(Operation::PidfdOpen, err))?;
+        Self::open(pid as u32)
+    }
+
+    /// Returns the process id the handle was opened for.
+    #[inline]
+    pub const fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Extract the raw file descriptor.
+    #[inline]
+    pub const fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Returns `true` if the process has not been reaped yet, and so its pid
+    /// still refers to it.
+    pub fn is_alive(&self) -> Result<bool, Error> {
+        loop {
+            match unsafe {
+                syscall!([ro] Sysno::pidfd_send_signal, self.fd, 0, core::ptr::null::<u8>(), 0)
+            } {
+                Err(Errno::EINTR) => (),
+                // The process exists, we are just not allowed to signal it.
+                Ok(_) | Err(Errno::EPERM) => return Ok(true),
+                Err(Errno::ESRCH) => return Ok(false),
+                Err(err) => return Err(Error::new(Operation::PidfdSendSignal, err)),
+            }
+        }
+    }
+
+    /// Calls `f` with the handle's pid and discards its result if the process
+    /// exited in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// Returns a process gone error if the process is gone, whether `f`
+    /// failed or read informations of a process that reused the pid.
+    pub(crate) fn with_pid<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(u32) -> Result<T, Error>,
+    {
+        let res = f(self.pid);
+
+        if self.is_alive()? {
+            res
+        } else {
+            Err(res
+                .err()
+                .unwrap_or(Error::new(Operation::Lookup, Errno::ESRCH))
+                .process_gone())
+        }
+    }
+}
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        _ = unsafe { syscall!([ro] Sysno::close, self.fd) };
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::os::fd::AsRawFd for ProcessHandle {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::os::fd::AsFd for ProcessHandle {
+    #[inline]
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        unsafe { std::os::fd::BorrowedFd::borrow_raw(self.fd) }
+    }
+}
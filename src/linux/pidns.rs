@@ -0,0 +1,301 @@
+use core::{fmt, ops::Deref};
+
+use crate::{CStr, Dev, Dir, DirentBuf, Errno, Error, Operation, ProcessHandle, RawFd};
+use atoi::FromRadix10;
+use linux_stat::{fstatat_cstr, StatAtFlags, CURRENT_DIRECTORY};
+
+use super::{
+    procfs::{check_gone, parse_pid, with_pid_path, with_proc_path, File},
+    DirBuf,
+};
+
+/// Maximum nesting of pid namespaces supported by the kernel.
+const MAX_PID_NS_LEVEL: usize = 32;
+
+/// The ids of a process in every pid namespace it is visible from, from the
+/// namespace `/proc` was mounted in to the process' own one.
+#[derive(Clone, Copy)]
+pub struct NsIds {
+    ids: [u32; MAX_PID_NS_LEVEL],
+    len: usize,
+}
+
+impl NsIds {
+    /// Parses a tab separated list of ids as found in `/proc/<pid>/status`.
+    fn parse(mut buf: &[u8]) -> Result<Self, Errno> {
+        let mut res = Self {
+            ids: [0; MAX_PID_NS_LEVEL],
+            len: 0,
+        };
+
+        loop {
+            let (id, len) = u32::from_radix_10(buf);
+            if len == 0 || res.len == MAX_PID_NS_LEVEL {
+                return Err(Errno::EINVAL);
+            }
+            res.ids[res.len] = id;
+            res.len += 1;
+
+            buf = &buf[len..];
+            match buf.first() {
+                None => return Ok(res),
+                Some(b'\t') | Some(b' ') => buf = &buf[1..],
+                Some(_) => return Err(Errno::EINVAL),
+            }
+        }
+    }
+
+    /// Extracts a slice containing all the ids.
+    #[inline]
+    pub fn as_slice(&self) -> &[u32] {
+        &self.ids[..self.len]
+    }
+
+    /// Returns the id in the pid namespace `/proc` was mounted in.
+    #[inline]
+    pub fn outermost(&self) -> u32 {
+        self.ids[0]
+    }
+
+    /// Returns the id in the process' own pid namespace.
+    #[inline]
+    pub fn innermost(&self) -> u32 {
+        self.ids[self.len - 1]
+    }
+}
+
+impl Deref for NsIds {
+    type Target = [u32];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl PartialEq for NsIds {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for NsIds {}
+
+impl core::hash::Hash for NsIds {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state)
+    }
+}
+
+/// Serializes the ids as a sequence.
+#[cfg(feature = "serde")]
+impl serde::Serialize for NsIds {
+    #[inline]
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.as_slice())
+    }
+}
+
+impl fmt::Debug for NsIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+/// A process' ids in every nested pid namespace, read from the `NSpid`,
+/// `NSpgid` and `NSsid` fields of `/proc/<pid>/status` (Linux 4.1+).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NsProcessInfo {
+    /// The process ids.
+    pub pid: NsIds,
+    /// The process group ids.
+    pub pgrp: NsIds,
+    /// The session ids.
+    pub session: NsIds,
+}
+
+impl NsProcessInfo {
+    fn parse_at(dirfd: RawFd, path: &CStr) -> Result<Self, Error> {
+        let mut pid = None;
+        let mut pgrp = None;
+        let mut session = None;
+
+        File::open_at(dirfd, path)?
+            .for_each_field(|key, value| {
+                match key {
+                    b"NSpid" => pid = Some(NsIds::parse(value)),
+                    b"NSpgid" => pgrp = Some(NsIds::parse(value)),
+                    b"NSsid" => session = Some(NsIds::parse(value)),
+                    _ => (),
+                }
+                pid.is_none() || pgrp.is_none() || session.is_none()
+            })
+            .map_err(|err| err.or_path(path.to_bytes()))?;
+
+        let parse_error = |err| Error::with_path(Operation::Parse, path.to_bytes(), err);
+        match (pid, pgrp, session) {
+            (Some(pid), Some(pgrp), Some(session)) => Ok(Self {
+                pid: pid.map_err(parse_error)?,
+                pgrp: pgrp.map_err(parse_error)?,
+                session: session.map_err(parse_error)?,
+            }),
+            _ => Err(parse_error(Errno::ENODATA)),
+        }
+    }
+
+    /// Maps the [Errno::ENODATA] error of kernels not reporting namespaced
+    /// ids to [None].
+    pub(crate) fn optional(res: Result<Self, Error>) -> Result<Option<Self>, Error> {
+        match res {
+            Err(err) if err.operation() == Operation::Parse && err.errno() == Errno::ENODATA => {
+                Ok(None)
+            }
+            other => other.map(Some),
+        }
+    }
+
+    /// Returns the informations for the current process.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Errno::ENODATA] if the kernel does not report namespaced ids.
+    #[inline]
+    pub fn current() -> Result<Self, Error> {
+        with_proc_path(None, b"status", |path| {
+            Self::parse_at(CURRENT_DIRECTORY, path)
+        })
+    }
+
+    /// Returns the informations for the `pid` process.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Errno::ENODATA] if the kernel does not report namespaced ids
+    /// and an error for which [Error::is_process_gone] is `true` if the
+    /// process does not exist or exited while being read.
+    #[inline]
+    pub fn for_process(pid: u32) -> Result<Self, Error> {
+        with_proc_path(Some(pid), b"status", |path| {
+            Self::parse_at(CURRENT_DIRECTORY, path)
+        })
+        .map_err(|err| check_gone(pid, err))
+    }
+
+    /// Returns the informations for the process referred by `handle`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process exited.
+    #[inline]
+    pub fn for_handle(handle: &ProcessHandle) -> Result<Self, Error> {
+        handle.with_pid(Self::for_process)
+    }
+
+    /// Finds the process whose id is `pid` in the pid namespace of the
+    /// `namespace_of` process, using `dirent_buf` to scan `/proc`.
+    ///
+    /// The id of the found process in the current namespace is
+    /// `pid.outermost()`. Processes in namespaces nested in the one of
+    /// `namespace_of` are not considered.
+    pub fn find_in_namespace_with_buffer<B: DirentBuf>(
+        pid: u32,
+        namespace_of: u32,
+        dirent_buf: &mut B,
+    ) -> Result<Option<Self>, Error> {
+        let level = Self::for_process(namespace_of)?.pid.len();
+        let ns = with_proc_path(Some(namespace_of), b"ns/pid", |path| {
+            namespace_id(CURRENT_DIRECTORY, path)
+        })?;
+
+        let mut proc = Dir::open(c"/proc")?;
+        let fd = proc.as_raw_fd();
+        let mut entries = proc.iter(dirent_buf)?;
+        while let Some(entry) = entries.next() {
+            let candidate = match parse_pid(entry?.name()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+
+            let info = match with_pid_path(candidate, b"status", |path| Self::parse_at(fd, path)) {
+                Err(err) if check_gone(candidate, err).is_process_gone() => continue,
+                other => other?,
+            };
+            if info.pid.len() != level || info.pid.innermost() != pid {
+                continue;
+            }
+
+            match with_pid_path(candidate, b"ns/pid", |path| namespace_id(fd, path)) {
+                // The links of other users' processes need ptrace access.
+                Err(err) if matches!(err.errno(), Errno::EACCES | Errno::EPERM) => continue,
+                Err(err) if check_gone(candidate, err).is_process_gone() => continue,
+                Ok(id) if id == ns => return Ok(Some(info)),
+                other => _ = other?,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Same as [Self::find_in_namespace_with_buffer] but with default buffer.
+    #[inline]
+    pub fn find_in_namespace(pid: u32, namespace_of: u32) -> Result<Option<Self>, Error> {
+        Self::find_in_namespace_with_buffer(pid, namespace_of, &mut DirBuf::new())
+    }
+}
+
+/// Returns the device and inode identifying the namespace `path` links to.
+fn namespace_id(dirfd: RawFd, path: &CStr) -> Result<(Dev, u64), Error> {
+    loop {
+        match unsafe { fstatat_cstr(dirfd, path, StatAtFlags::empty()) } {
+            Err(Errno::EINTR) => (),
+            Err(err) => return Err(Error::with_path(Operation::Fstatat, path.to_bytes(), err)),
+            Ok(md) => return Ok((md.dev(), md.inode())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn parse_ns_ids() {
+        assert_eq!(NsIds::parse(b"42").unwrap().as_slice(), &[42]);
+        let ids = NsIds::parse(b"1234\t56\t1").unwrap();
+        assert_eq!(ids.as_slice(), &[1234, 56, 1]);
+        assert_eq!(ids.outermost(), 1234);
+        assert_eq!(ids.innermost(), 1);
+
+        assert!(NsIds::parse(b"").is_err());
+        assert!(NsIds::parse(b"1\t").is_err());
+        assert!(NsIds::parse(b"1\tx").is_err());
+        assert!(NsIds::parse(b"1,2").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_ns_ids() {
+        let ids = NsIds::parse(b"1234\t56").unwrap();
+        assert_eq!(serde_json::to_string(&ids).unwrap(), "[1234,56]");
+    }
+
+    #[test]
+    fn current_ns_process_info() {
+        let info = match NsProcessInfo::current() {
+            Err(err) if err.errno() == Errno::ENODATA => return,
+            other => other.unwrap(),
+        };
+        assert_eq!(info.pid.innermost(), std::process::id());
+
+        let found = NsProcessInfo::find_in_namespace(info.pid.innermost(), info.pid.outermost())
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, info);
+    }
+}
@@ -1,14 +1,20 @@
 use core::{fmt, mem::MaybeUninit};
 
-use crate::{CStr, Dev, DirentBuf, Errno, RawFd, TtyInfo};
+use crate::{
+    CStr, Dev, DirentBuf, Errno, Error, NsProcessInfo, Operation, ProcessHandle, RawFd, TtyInfo,
+};
 use atoi::FromRadix10Signed;
-use linux_raw_sys::general::{O_CLOEXEC, O_RDONLY};
 use linux_stat::CURRENT_DIRECTORY;
-use linux_syscalls::{syscall, Sysno};
 
-use super::{DirBuf, PathBuf};
+use super::{
+    procfs::{check_gone, File},
+    DirBuf, PathBuf,
+};
 
-const SELF_INFO_PATH: &[u8] = b"/proc/self/stat\0".as_slice();
+/// Large enough for a stat line with every field at its maximum width.
+const STAT_BUF_LEN: usize = 2048;
+/// Number of fields in a stat line since Linux 2.6.0.
+const MIN_STAT_FIELDS: usize = 41;
 
 unsafe fn parse_num<T: FromRadix10Signed>(buf: &[u8]) -> Result<(T, &[u8]), Errno> {
     let (res, len) = T::from_radix_10_signed(buf);
@@ -20,7 +26,7 @@ unsafe fn parse_num<T: FromRadix10Signed>(buf: &[u8]) -> Result<(T, &[u8]), Errn
 }
 
 unsafe fn skip_char(buf: &[u8], ch: u8) -> Result<&[u8], Errno> {
-    if buf.iter().copied().next().map_or(false, |c| c == ch) {
+    if buf.first() == Some(&ch) {
         Ok(buf.get_unchecked(1..))
     } else {
         Err(Errno::EINVAL)
@@ -34,66 +40,62 @@ unsafe fn skip_space(buf: &[u8]) -> Result<&[u8], Errno> {
 
 /// A process' informations useful to get tty informations.
 #[derive(Debug, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RawProcessInfo {
     /// The process id.
     pub pid: u32,
+    /// The process group id.
+    pub pgrp: u32,
     /// The session id.
     pub session: u32,
     /// The tty device id if process has one.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_dev::option"))]
     pub tty: Option<Dev>,
 }
 
 impl RawProcessInfo {
-    fn parse(path: &CStr) -> Result<Self, Errno> {
-        let path = path.as_ptr();
-
-        unsafe {
-            struct FdHolder(RawFd);
-            impl Drop for FdHolder {
-                fn drop(&mut self) {
-                    _ = unsafe { syscall!([ro] Sysno::close, self.0) };
-                }
-            }
+    pub(crate) fn parse_at(dirfd: RawFd, path: &CStr) -> Result<Self, Error> {
+        let mut buf = [0u8; STAT_BUF_LEN];
+        let buf = File::open_at(dirfd, path)?
+            .read_to_fill(&mut buf)
+            .map_err(|err| err.or_path(path.to_bytes()))?;
+        Self::parse_stat(buf)
+            .map_err(|err| Error::with_path(Operation::Parse, path.to_bytes(), err))
+    }
 
-            let mut buf = MaybeUninit::<[u8; 1024]>::uninit();
-            let mut len: usize = 0;
-            {
-                let flags = O_RDONLY | O_CLOEXEC;
-
-                let fd = loop {
-                    match syscall!([ro] Sysno::openat, CURRENT_DIRECTORY, path, flags) {
-                        Err(Errno::EINTR) => (),
-                        Err(err) => return Err(err),
-                        Ok(fd) => break fd as RawFd,
-                    }
-                };
-
-                let _h = FdHolder(fd);
-                let mut b = core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), 1024);
-                while !b.is_empty() {
-                    match syscall!(Sysno::read, fd, b.as_mut_ptr(), b.len()) {
-                        Ok(0) => break,
-                        Ok(n) => {
-                            len += n;
-                            b = b.get_unchecked_mut(n..);
-                        }
-                        Err(Errno::EINTR) => (),
-                        Err(err) => return Err(err),
-                    }
-                }
-            }
-            let buf = buf.assume_init();
-            let buf = buf.get_unchecked(..len);
+    /// Parses the content of a `/proc/<pid>/stat` file.
+    ///
+    /// Returns [Errno::ENODATA] if `buf` is not a complete, newline
+    /// terminated, line and [Errno::EINVAL] if it is malformed.
+    fn parse_stat(buf: &[u8]) -> Result<Self, Errno> {
+        let buf = match buf.split_last() {
+            Some((b'\n', line)) => line,
+            _ => return Err(Errno::ENODATA),
+        };
 
+        unsafe {
             let (pid, buf) = parse_num(buf)?;
             let buf = skip_space(buf)?;
 
+            // `comm` can contain any byte, `)` included, but no field after it
+            // can, so the last `)` is the closing one.
             let buf = skip_char(buf, b'(')?;
-            let buf = match memchr::memchr(b')', buf) {
+            let buf = match memchr::memrchr(b')', buf) {
                 Some(i) => skip_space(buf.get_unchecked((i + 1)..))?,
                 None => return Err(Errno::EINVAL),
             };
 
+            let mut fields = 2;
+            for field in buf.split(|&c| c == b' ') {
+                if field.is_empty() {
+                    return Err(Errno::EINVAL);
+                }
+                fields += 1;
+            }
+            if fields < MIN_STAT_FIELDS {
+                return Err(Errno::EINVAL);
+            }
+
             let buf = match memchr::memchr(b' ', buf) {
                 Some(1) => buf.get_unchecked(2..),
                 Some(_) | None => return Err(Errno::EINVAL),
@@ -101,21 +103,21 @@ impl RawProcessInfo {
 
             let (_, buf) = parse_num::<core::ffi::c_int>(buf)?;
             let buf = skip_space(buf)?;
-            let buf = match memchr::memchr(b' ', buf) {
-                Some(0) | None => return Err(Errno::EINVAL),
-                Some(n) => buf.get_unchecked((n + 1)..),
-            };
+            let (pgrp, buf) = parse_num(buf)?;
+            let buf = skip_space(buf)?;
             let (session, buf) = parse_num(buf)?;
             let buf = skip_space(buf)?;
             let tty_nr = parse_num::<i32>(buf)?.0;
-            let tty_nr = if tty_nr == -1 {
+            // The kernel reports `0` for processes without a terminal.
+            let tty_nr = if tty_nr == 0 || tty_nr == -1 {
                 None
             } else {
-                Some(core::mem::transmute::<i32, u32>(tty_nr).into())
+                Some((tty_nr as u32).into())
             };
 
             Ok(Self {
                 pid,
+                pgrp,
                 session,
                 tty: tty_nr,
             })
@@ -124,12 +126,17 @@ impl RawProcessInfo {
 
     /// Returns the informations for the current process.
     #[inline]
-    pub fn current() -> Result<Self, Errno> {
-        Self::parse(unsafe { CStr::from_ptr(SELF_INFO_PATH.as_ptr().cast()) })
+    pub fn current() -> Result<Self, Error> {
+        Self::parse_at(CURRENT_DIRECTORY, c"/proc/self/stat")
     }
 
     /// Returns the informations for the `pid` process.
-    pub fn for_process(pid: u32) -> Result<Self, Errno> {
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process does not exist or exited while being read.
+    pub fn for_process(pid: u32) -> Result<Self, Error> {
         use itoap::Integer;
 
         let mut uninit_buf = MaybeUninit::<[u8; 11 + core::ffi::c_int::MAX_LEN + 1]>::uninit();
@@ -144,38 +151,87 @@ impl RawProcessInfo {
             CStr::from_ptr((uninit_buf.as_mut_ptr().cast::<u8>() as *const u8).cast())
         };
 
-        Self::parse(path)
+        Self::parse_at(CURRENT_DIRECTORY, path).map_err(|err| check_gone(pid, err))
+    }
+
+    /// Returns the informations for the `tid` thread of the `pid` process.
+    ///
+    /// The returned `pid` is the thread id. Errors for exited threads are
+    /// reported as for [Self::for_process].
+    pub fn for_thread(pid: u32, tid: u32) -> Result<Self, Error> {
+        use itoap::Integer;
+
+        let mut uninit_buf = MaybeUninit::<[u8; 17 + 2 * core::ffi::c_int::MAX_LEN + 1]>::uninit();
+        let path = unsafe {
+            let mut buf = uninit_buf.as_mut_ptr().cast::<u8>();
+            core::ptr::copy_nonoverlapping(b"/proc/".as_ptr().cast::<u8>(), buf, 6);
+            buf = buf.add(6);
+            buf = buf.add(itoap::write_to_ptr(buf, pid));
+            core::ptr::copy_nonoverlapping(b"/task/".as_ptr().cast::<u8>(), buf, 6);
+            buf = buf.add(6);
+            buf = buf.add(itoap::write_to_ptr(buf, tid));
+            core::ptr::copy_nonoverlapping(b"/stat".as_ptr().cast::<u8>(), buf, 5);
+            *buf.add(5) = 0;
+            CStr::from_ptr((uninit_buf.as_mut_ptr().cast::<u8>() as *const u8).cast())
+        };
+
+        Self::parse_at(CURRENT_DIRECTORY, path).map_err(|err| check_gone(tid, err))
+    }
+
+    /// Returns the informations for the process referred by `handle`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process exited, even if its pid has already been reused by another
+    /// process.
+    #[inline]
+    pub fn for_handle(handle: &ProcessHandle) -> Result<Self, Error> {
+        handle.with_pid(Self::for_process)
     }
 }
 
 /// [RawProcessInfo] with `tty` field remapped to [TtyInfo].
 #[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(bound(serialize = ""))
+)]
 pub struct ProcessInfo<B: DirentBuf> {
     /// The process id.
     pub pid: u32,
+    /// The process group id.
+    pub pgrp: u32,
     /// The session id.
     pub session: u32,
     /// The tty device informations if process has one.
     pub tty: Option<TtyInfo<B>>,
+    /// The ids in every nested pid namespace, [None] if the kernel does not
+    /// report them (before Linux 4.1).
+    pub namespaced: Option<NsProcessInfo>,
 }
 
 impl<B: DirentBuf> fmt::Debug for ProcessInfo<B> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ProcessInfo")
             .field("pid", &self.pid)
+            .field("pgrp", &self.pgrp)
             .field("session", &self.session)
             .field("tty", &self.tty)
+            .field("namespaced", &self.namespaced)
             .finish()
     }
 }
 
 impl<B: DirentBuf> ProcessInfo<B> {
-    /// Calls [RawProcessInfo::current] and maps `tty` with [TtyInfo::by_device_with_buffers_in].
+    /// Calls [RawProcessInfo::current] and [NsProcessInfo::current], and maps `tty`
+    /// with [TtyInfo::by_device_with_buffers_in].
     pub fn current_with_buffers_in<'a, I, B1>(
         dirs: I,
         dirent_buf: &mut B1,
         path_buf: B,
-    ) -> Result<Self, Errno>
+    ) -> Result<Self, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
         B1: DirentBuf,
@@ -184,21 +240,24 @@ impl<B: DirentBuf> ProcessInfo<B> {
 
         Ok(Self {
             pid: raw.pid,
+            pgrp: raw.pgrp,
             session: raw.session,
             tty: raw
                 .tty
                 .map(|rdev| TtyInfo::by_device_with_buffers_in(rdev, dirs, dirent_buf, path_buf))
                 .transpose()?,
+            namespaced: NsProcessInfo::optional(NsProcessInfo::current())?,
         })
     }
 
-    /// Calls [RawProcessInfo::for_process] and maps `tty` with [TtyInfo::by_device_with_buffers_in].
+    /// Calls [RawProcessInfo::for_process] and [NsProcessInfo::for_process], and maps `tty`
+    /// with [TtyInfo::by_device_with_buffers_in].
     pub fn for_process_with_buffers_in<'a, I, B1>(
         pid: u32,
         dirs: I,
         dirent_buf: &mut B1,
         path_buf: B,
-    ) -> Result<Self, Errno>
+    ) -> Result<Self, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
         B1: DirentBuf,
@@ -207,17 +266,68 @@ impl<B: DirentBuf> ProcessInfo<B> {
 
         Ok(Self {
             pid: raw.pid,
+            pgrp: raw.pgrp,
             session: raw.session,
             tty: raw
                 .tty
                 .map(|rdev| TtyInfo::by_device_with_buffers_in(rdev, dirs, dirent_buf, path_buf))
                 .transpose()?,
+            namespaced: NsProcessInfo::optional(NsProcessInfo::for_process(pid))?,
         })
     }
 
+    /// Calls [RawProcessInfo::for_handle] and [NsProcessInfo::for_handle], and maps `tty`
+    /// with [TtyInfo::by_device_with_buffers_in].
+    pub fn for_handle_with_buffers_in<'a, I, B1>(
+        handle: &ProcessHandle,
+        dirs: I,
+        dirent_buf: &mut B1,
+        path_buf: B,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+        B1: DirentBuf,
+    {
+        let raw = RawProcessInfo::for_handle(handle)?;
+
+        Ok(Self {
+            pid: raw.pid,
+            pgrp: raw.pgrp,
+            session: raw.session,
+            tty: raw
+                .tty
+                .map(|rdev| TtyInfo::by_device_with_buffers_in(rdev, dirs, dirent_buf, path_buf))
+                .transpose()?,
+            namespaced: NsProcessInfo::optional(NsProcessInfo::for_handle(handle))?,
+        })
+    }
+
+    /// Calls [NsProcessInfo::find_in_namespace_with_buffer] and
+    /// [Self::for_process_with_buffers_in] with the found process id.
+    ///
+    /// Returns [None] if no process has id `pid` in the pid namespace of the
+    /// `namespace_of` process.
+    pub fn for_namespaced_process_with_buffers_in<'a, I, B1>(
+        pid: u32,
+        namespace_of: u32,
+        dirs: I,
+        dirent_buf: &mut B1,
+        path_buf: B,
+    ) -> Result<Option<Self>, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+        B1: DirentBuf,
+    {
+        NsProcessInfo::find_in_namespace_with_buffer(pid, namespace_of, dirent_buf)?
+            .map(|info| {
+                Self::for_process_with_buffers_in(info.pid.outermost(), dirs, dirent_buf, path_buf)
+            })
+            .transpose()
+    }
+
     /// Calls [RawProcessInfo::current] and maps `tty` with [TtyInfo::by_device_with_buffers].
     #[inline]
-    pub fn current_with_buffers<B1>(dirent_buf: &mut B1, path_buf: B) -> Result<Self, Errno>
+    pub fn current_with_buffers<B1>(dirent_buf: &mut B1, path_buf: B) -> Result<Self, Error>
     where
         B1: DirentBuf,
     {
@@ -230,7 +340,7 @@ impl<B: DirentBuf> ProcessInfo<B> {
         pid: u32,
         dirent_buf: &mut B1,
         path_buf: B,
-    ) -> Result<Self, Errno>
+    ) -> Result<Self, Error>
     where
         B1: DirentBuf,
     {
@@ -238,12 +348,50 @@ impl<B: DirentBuf> ProcessInfo<B> {
             Self::for_process_with_buffers_in(pid, dirs, dirent_buf, path_buf)
         })
     }
+
+    /// Same as [Self::for_namespaced_process_with_buffers_in] but with
+    /// default `dirs` ('/dev').
+    #[inline]
+    pub fn for_namespaced_process_with_buffers<B1>(
+        pid: u32,
+        namespace_of: u32,
+        dirent_buf: &mut B1,
+        path_buf: B,
+    ) -> Result<Option<Self>, Error>
+    where
+        B1: DirentBuf,
+    {
+        crate::with_default_paths(|dirs| {
+            Self::for_namespaced_process_with_buffers_in(
+                pid,
+                namespace_of,
+                dirs,
+                dirent_buf,
+                path_buf,
+            )
+        })
+    }
+
+    /// Calls [RawProcessInfo::for_handle] and maps `tty` with [TtyInfo::by_device_with_buffers].
+    #[inline]
+    pub fn for_handle_with_buffers<B1>(
+        handle: &ProcessHandle,
+        dirent_buf: &mut B1,
+        path_buf: B,
+    ) -> Result<Self, Error>
+    where
+        B1: DirentBuf,
+    {
+        crate::with_default_paths(|dirs| {
+            Self::for_handle_with_buffers_in(handle, dirs, dirent_buf, path_buf)
+        })
+    }
 }
 
 impl ProcessInfo<PathBuf> {
     /// Calls [RawProcessInfo::current] and maps `tty` with [TtyInfo::by_device_in].
     #[inline]
-    pub fn current_in<'a, I>(dirs: I) -> Result<Self, Errno>
+    pub fn current_in<'a, I>(dirs: I) -> Result<Self, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
     {
@@ -252,7 +400,7 @@ impl ProcessInfo<PathBuf> {
 
     /// Calls [RawProcessInfo::for_process] and maps `tty` with [TtyInfo::by_device_in].
     #[inline]
-    pub fn for_process_in<'a, I>(pid: u32, dirs: I) -> Result<Self, Errno>
+    pub fn for_process_in<'a, I>(pid: u32, dirs: I) -> Result<Self, Error>
     where
         I: IntoIterator<Item = &'a CStr>,
     {
@@ -261,13 +409,308 @@ impl ProcessInfo<PathBuf> {
 
     /// Calls [RawProcessInfo::current] and maps `tty` with [TtyInfo::by_device].
     #[inline]
-    pub fn current() -> Result<Self, Errno> {
+    pub fn current() -> Result<Self, Error> {
         Self::current_with_buffers(&mut DirBuf::new(), PathBuf::new())
     }
 
     /// Calls [RawProcessInfo::for_process] and maps `tty` with [TtyInfo::by_device].
     #[inline]
-    pub fn for_process(pid: u32) -> Result<Self, Errno> {
+    pub fn for_process(pid: u32) -> Result<Self, Error> {
         Self::for_process_with_buffers(pid, &mut DirBuf::new(), PathBuf::new())
     }
+
+    /// Same as [Self::for_namespaced_process_with_buffers_in] but with
+    /// default buffers.
+    #[inline]
+    pub fn for_namespaced_process_in<'a, I>(
+        pid: u32,
+        namespace_of: u32,
+        dirs: I,
+    ) -> Result<Option<Self>, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+    {
+        Self::for_namespaced_process_with_buffers_in(
+            pid,
+            namespace_of,
+            dirs,
+            &mut DirBuf::new(),
+            PathBuf::new(),
+        )
+    }
+
+    /// Same as [Self::for_namespaced_process_with_buffers_in] but with
+    /// default buffers and dirs.
+    #[inline]
+    pub fn for_namespaced_process(pid: u32, namespace_of: u32) -> Result<Option<Self>, Error> {
+        Self::for_namespaced_process_with_buffers(
+            pid,
+            namespace_of,
+            &mut DirBuf::new(),
+            PathBuf::new(),
+        )
+    }
+
+    /// Calls [RawProcessInfo::for_handle] and maps `tty` with [TtyInfo::by_device_in].
+    #[inline]
+    pub fn for_handle_in<'a, I>(handle: &ProcessHandle, dirs: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+    {
+        Self::for_handle_with_buffers_in(handle, dirs, &mut DirBuf::new(), PathBuf::new())
+    }
+
+    /// Calls [RawProcessInfo::for_handle] and maps `tty` with [TtyInfo::by_device].
+    #[inline]
+    pub fn for_handle(handle: &ProcessHandle) -> Result<Self, Error> {
+        Self::for_handle_with_buffers(handle, &mut DirBuf::new(), PathBuf::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::{format, vec::Vec};
+
+    /// Reads the adversarial `comm` values of `testdata/comm`, one per file:
+    /// the kernel copies them verbatim between the parentheses, up to 15
+    /// bytes.
+    fn comm_corpus() -> Vec<Vec<u8>> {
+        let dir = format!("{}/testdata/comm", env!("CARGO_MANIFEST_DIR"));
+        let mut paths = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| std::fs::read(path).unwrap())
+            .collect()
+    }
+
+    fn stat_line(pid: u32, comm: &[u8], session: u32, tty_nr: i32) -> Vec<u8> {
+        let mut line = format!("{pid} (").into_bytes();
+        line.extend_from_slice(comm);
+        line.extend_from_slice(
+            format!(
+                ") S 1 {pid} {session} {tty_nr} {pid} 4194560 1207 0 0 0 0 0 0 0 20 0 1 0 \
+                 68517 8523776 1293 18446744073709551615 94769013514240 94769014359565 \
+                 140723950880784 0 0 0 65536 3686404 1266761467 1 0 0 17 3 0 0 0 0 0 \
+                 94769014587088 94769014636084 94769029066752 140723950888361 \
+                 140723950888371 140723950888371 140723950891003 0\n"
+            )
+            .as_bytes(),
+        );
+        line
+    }
+
+    #[test]
+    fn parse_stat_corpus() {
+        for (i, comm) in comm_corpus().iter().enumerate() {
+            let pid = 1000 + i as u32;
+            let line = stat_line(pid, comm, 42, 34816);
+            let info = RawProcessInfo::parse_stat(&line).unwrap();
+            assert_eq!(info.pid, pid, "comm {comm:?}");
+            assert_eq!(info.pgrp, pid, "comm {comm:?}");
+            assert_eq!(info.session, 42, "comm {comm:?}");
+            assert_eq!(info.tty, Some(Dev::from_u32(34816)), "comm {comm:?}");
+        }
+    }
+
+    #[test]
+    fn parse_stat_no_tty() {
+        let line = stat_line(1, b"init", 1, -1);
+        assert!(RawProcessInfo::parse_stat(&line).unwrap().tty.is_none());
+        let line = stat_line(1, b"init", 1, 0);
+        assert!(RawProcessInfo::parse_stat(&line).unwrap().tty.is_none());
+    }
+
+    #[test]
+    fn parse_stat_truncated() {
+        for comm in &comm_corpus() {
+            let line = stat_line(7, comm, 7, 1025);
+            for len in 0..line.len() {
+                let prefix = &line[..len];
+                match RawProcessInfo::parse_stat(prefix) {
+                    Err(Errno::ENODATA) | Err(Errno::EINVAL) => (),
+                    other => panic!("prefix {prefix:?} parsed as {other:?}"),
+                }
+                if prefix.last() != Some(&b'\n') {
+                    assert_eq!(
+                        RawProcessInfo::parse_stat(prefix).unwrap_err(),
+                        Errno::ENODATA
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_stat_malformed() {
+        for line in [
+            &b"\n"[..],
+            b"1 bash) S 1 1 1 0\n",
+            b"1 (bash S 1 1 1 0\n",
+            b"x (bash) S 1 1 1 0\n",
+            b"1 (bash) S 1 1 1 0\n",
+            b"1  (bash) S 1 1 1 0\n",
+        ] {
+            assert_eq!(RawProcessInfo::parse_stat(line).unwrap_err(), Errno::EINVAL);
+        }
+
+        let line = stat_line(1, b"bash", 1, 0);
+        let mut doubled = line.clone();
+        doubled.insert(10, b' ');
+        assert_eq!(
+            RawProcessInfo::parse_stat(&doubled).unwrap_err(),
+            Errno::EINVAL
+        );
+
+        let mut state = line.clone();
+        state.insert(9, b'S');
+        assert_eq!(
+            RawProcessInfo::parse_stat(&state).unwrap_err(),
+            Errno::EINVAL
+        );
+    }
+
+    #[test]
+    fn parse_stat_fuzz() {
+        const ALPHABET: &[u8] = b"() \n\tab0)(";
+
+        // xorshift, deterministic so failures are reproducible.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let len = (next() % 16) as usize;
+            let comm = (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect::<Vec<_>>();
+            let pid = (next() % 4_194_304) as u32;
+            let session = (next() % 4_194_304) as u32;
+            let tty_nr = (next() % 65535 + 1) as i32;
+
+            let info = RawProcessInfo::parse_stat(&stat_line(pid, &comm, session, tty_nr))
+                .unwrap_or_else(|err| panic!("comm {comm:?}: {err:?}"));
+            assert_eq!(info.pid, pid);
+            assert_eq!(info.session, session);
+            assert_eq!(info.tty, Some(Dev::from_u32(tty_nr as u32)));
+        }
+    }
+
+    #[test]
+    fn handle_process_info() {
+        let handle = match ProcessHandle::current() {
+            Err(err) if err.errno() == Errno::ENOSYS => return,
+            other => other.unwrap(),
+        };
+        assert_eq!(handle.pid(), std::process::id());
+        assert!(handle.is_alive().unwrap());
+        let info = RawProcessInfo::for_handle(&handle).unwrap();
+        assert_eq!(info.pid, std::process::id());
+
+        #[cfg(feature = "std")]
+        {
+            use std::os::fd::{AsFd, AsRawFd};
+
+            assert_eq!(AsRawFd::as_raw_fd(&handle), handle.as_raw_fd());
+            let fd = handle.as_fd().try_clone_to_owned().unwrap();
+            assert_ne!(fd.as_raw_fd(), handle.as_raw_fd());
+        }
+    }
+
+    #[test]
+    fn handle_exited_process() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let handle = ProcessHandle::open(child.id());
+        child.wait().unwrap();
+        let handle = match handle {
+            Err(err) if err.errno() == Errno::ENOSYS => return,
+            other => other.unwrap(),
+        };
+        assert!(!handle.is_alive().unwrap());
+        assert_eq!(
+            RawProcessInfo::for_handle(&handle).unwrap_err().errno(),
+            Errno::ESRCH
+        );
+    }
+
+    #[test]
+    fn missing_process_error() {
+        let err = RawProcessInfo::for_process(u32::MAX).unwrap_err();
+        assert!(err.is_process_gone());
+        assert_eq!(err.operation(), Operation::Lookup);
+        assert_eq!(err.path(), Some(&b"/proc/4294967295/stat"[..]));
+    }
+
+    #[test]
+    fn exited_process_is_gone() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        assert!(RawProcessInfo::for_process(pid)
+            .unwrap_err()
+            .is_process_gone());
+        assert!(crate::Credentials::for_process(pid)
+            .unwrap_err()
+            .is_process_gone());
+        assert!(crate::Tasks::for_process(pid)
+            .err()
+            .unwrap()
+            .is_process_gone());
+        match ProcessHandle::open(pid) {
+            Err(err) if err.errno() == Errno::ENOSYS => (),
+            res => assert!(res.err().unwrap().is_process_gone()),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_process_info() {
+        let raw = RawProcessInfo::parse_stat(&stat_line(42, b"sh", 7, 0x8803)).unwrap();
+        let json = serde_json::to_string(&raw).unwrap();
+        assert_eq!(
+            json,
+            r#"{"pid":42,"pgrp":42,"session":7,"tty":{"major":136,"minor":3}}"#
+        );
+        let parsed: RawProcessInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.tty, raw.tty);
+
+        let info = ProcessInfo::<PathBuf> {
+            pid: 42,
+            pgrp: 42,
+            session: 7,
+            tty: None,
+            namespaced: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&info).unwrap(),
+            r#"{"pid":42,"pgrp":42,"session":7,"tty":null,"namespaced":null}"#
+        );
+    }
+
+    #[test]
+    fn current_process_info() {
+        let info = RawProcessInfo::current().unwrap();
+        assert_eq!(info.pid, std::process::id());
+    }
+
+    #[test]
+    fn current_namespaced_ids() {
+        let info = ProcessInfo::current().unwrap();
+        assert_eq!(info.pid, std::process::id());
+        if let Some(ns) = info.namespaced {
+            assert_eq!(ns.pid.outermost(), info.pid);
+            assert_eq!(ns.session.outermost(), info.session);
+        }
+    }
 }
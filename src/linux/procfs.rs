@@ -0,0 +1,297 @@
+use core::mem::MaybeUninit;
+
+use crate::{CStr, Errno, Error, Operation, RawFd};
+use atoi::FromRadix10Checked;
+use linux_raw_sys::general::{O_CLOEXEC, O_RDONLY};
+use linux_stat::{fstatat_cstr, StatAtFlags, CURRENT_DIRECTORY};
+use linux_syscalls::{syscall, Sysno};
+
+use super::MAX_U32_LENGTH;
+
+const MAX_SUFFIX_LENGTH: usize = 32;
+
+/// Size of the buffer used to read files line by line.
+const FIELDS_BUF_LEN: usize = 1024;
+
+/// Calls `f` with `/proc/<pid>/<suffix>`, or `/proc/self/<suffix>` if `pid`
+/// is [None].
+///
+/// `suffix` must not be longer than 32 bytes.
+#[inline]
+pub(crate) fn with_proc_path<T, F>(pid: Option<u32>, suffix: &[u8], f: F) -> T
+where
+    F: FnOnce(&CStr) -> T,
+{
+    with_path(b"/proc/", pid, suffix, f)
+}
+
+/// Calls `f` with `<pid>/<suffix>`, to be used relative to a `/proc` [Dir].
+///
+/// `suffix` must not be longer than 32 bytes.
+///
+/// [Dir]: crate::Dir
+#[inline]
+pub(crate) fn with_pid_path<T, F>(pid: u32, suffix: &[u8], f: F) -> T
+where
+    F: FnOnce(&CStr) -> T,
+{
+    with_path(b"", Some(pid), suffix, f)
+}
+
+fn with_path<T, F>(prefix: &[u8], pid: Option<u32>, suffix: &[u8], f: F) -> T
+where
+    F: FnOnce(&CStr) -> T,
+{
+    debug_assert!(prefix.len() <= 6);
+    debug_assert!(suffix.len() <= MAX_SUFFIX_LENGTH);
+
+    let mut buf = MaybeUninit::<[u8; 6 + MAX_U32_LENGTH + 1 + MAX_SUFFIX_LENGTH + 1]>::uninit();
+    let path = unsafe {
+        let start = buf.as_mut_ptr().cast::<u8>();
+        let mut ptr = start;
+        let len = core::cmp::min(prefix.len(), 6);
+        core::ptr::copy_nonoverlapping(prefix.as_ptr(), ptr, len);
+        ptr = ptr.add(len);
+        match pid {
+            Some(pid) => ptr = ptr.add(itoap::write_to_ptr(ptr, pid)),
+            None => {
+                core::ptr::copy_nonoverlapping(b"self".as_ptr(), ptr, 4);
+                ptr = ptr.add(4);
+            }
+        }
+        *ptr = b'/';
+        ptr = ptr.add(1);
+        let len = core::cmp::min(suffix.len(), MAX_SUFFIX_LENGTH);
+        core::ptr::copy_nonoverlapping(suffix.as_ptr(), ptr, len);
+        *ptr.add(len) = 0;
+        CStr::from_ptr(start.cast())
+    };
+
+    f(path)
+}
+
+/// Turns `err`, coming from reading a `/proc/<pid>` file, into a process gone
+/// error if the process exited while being read.
+///
+/// The kernel reports reads from an exited process with [Errno::ESRCH], other
+/// errors (e.g. [Errno::ENOENT] or a half-read file) are checked against the
+/// existence of `/proc/<pid>`, so that a missing file or an unmounted `/proc`
+/// are not mistaken for an exit.
+pub(crate) fn check_gone(pid: u32, err: Error) -> Error {
+    match err.errno() {
+        Errno::ESRCH => err.process_gone(),
+        _ if !process_exists(pid) => err.process_gone(),
+        _ => err,
+    }
+}
+
+/// Returns `false` if `/proc/<pid>` is missing while `/proc` is mounted.
+fn process_exists(pid: u32) -> bool {
+    let exists = |path: &CStr| loop {
+        match unsafe { fstatat_cstr(CURRENT_DIRECTORY, path, StatAtFlags::empty()) } {
+            Err(Errno::EINTR) => (),
+            Err(Errno::ENOENT) | Err(Errno::ESRCH) => return false,
+            _ => return true,
+        }
+    };
+    with_proc_path(Some(pid), b"", exists) || !with_proc_path(None, b"", exists)
+}
+
+/// Parses the name of a `/proc` entry as a pid, `None` if it is not one.
+pub fn parse_pid(name: &CStr) -> Option<u32> {
+    let name = name.to_bytes();
+    match u32::from_radix_10_checked(name) {
+        (Some(pid), len) if len != 0 && len == name.len() => Some(pid),
+        _ => None,
+    }
+}
+
+/// A read-only file, closed when it goes out of scope.
+pub(crate) struct File(RawFd);
+
+impl File {
+    /// Opens `path` relative to `dirfd`.
+    pub fn open_at(dirfd: RawFd, path: &CStr) -> Result<Self, Error> {
+        let flags = O_RDONLY | O_CLOEXEC;
+
+        loop {
+            match unsafe { syscall!([ro] Sysno::openat, dirfd, path.as_ptr(), flags) } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::with_path(Operation::Open, path.to_bytes(), err)),
+                Ok(fd) => return Ok(Self(fd as RawFd)),
+            }
+        }
+    }
+
+    /// Reads at most `buf.len()` bytes, returning how many were read.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            match unsafe { syscall!(Sysno::read, self.0, buf.as_mut_ptr(), buf.len()) } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::new(Operation::Read, err)),
+                Ok(len) => return Ok(len),
+            }
+        }
+    }
+
+    /// Reads until `buf` is full or end of file is reached, returning the
+    /// read part of `buf`.
+    pub fn read_to_fill<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], Error> {
+        let mut len = 0;
+        while len < buf.len() {
+            match self.read(unsafe { buf.get_unchecked_mut(len..) })? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        Ok(unsafe { buf.get_unchecked(..len) })
+    }
+
+    /// Calls `f` with key and value of every `key: value` line until it
+    /// returns `false`.
+    ///
+    /// Leading whitespaces are stripped from values. Lines longer than the
+    /// internal buffer and lines without `:` are skipped.
+    #[inline]
+    pub fn for_each_field<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8], &[u8]) -> bool,
+    {
+        self.for_each_pair(b':', f)
+    }
+
+    /// Same as [Self::for_each_field] but with `separator` between keys and
+    /// values.
+    pub fn for_each_pair<F>(&self, separator: u8, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8], &[u8]) -> bool,
+    {
+        self.for_each_line(|line| match memchr::memchr(separator, line) {
+            Some(i) => {
+                let value = &line[(i + 1)..];
+                let skip = value.iter().take_while(|c| c.is_ascii_whitespace()).count();
+                f(&line[..i], &value[skip..])
+            }
+            None => true,
+        })
+    }
+
+    /// Calls `f` with every line, without the trailing newline, until it
+    /// returns `false`.
+    ///
+    /// Lines longer than the internal buffer and an unterminated last line
+    /// are skipped.
+    pub fn for_each_line<F>(&self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let mut buf = [0u8; FIELDS_BUF_LEN];
+        let mut len = 0;
+        let mut skipping = false;
+
+        loop {
+            let n = self.read(&mut buf[len..])?;
+            if n == 0 {
+                return Ok(());
+            }
+            len += n;
+
+            let mut start = 0;
+            while let Some(i) = memchr::memchr(b'\n', &buf[start..len]) {
+                let line = &buf[start..(start + i)];
+                start += i + 1;
+
+                if core::mem::take(&mut skipping) {
+                    continue;
+                }
+
+                if !f(line) {
+                    return Ok(());
+                }
+            }
+
+            if start == 0 && len == buf.len() {
+                skipping = true;
+                len = 0;
+            } else {
+                buf.copy_within(start..len, 0);
+                len -= start;
+            }
+        }
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        _ = unsafe { syscall!([ro] Sysno::close, self.0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::{ffi::CString, vec::Vec};
+
+    #[test]
+    fn missing_files_of_live_processes() {
+        let pid = std::process::id();
+        let err = Error::new(Operation::Open, Errno::ENOENT);
+        assert!(!check_gone(pid, err).is_process_gone());
+        let err = Error::new(Operation::Read, Errno::ESRCH);
+        assert!(check_gone(pid, err).is_process_gone());
+
+        // Above the largest `pid_max`.
+        let err = Error::new(Operation::Open, Errno::ENOENT);
+        assert!(check_gone(1 << 23, err).is_process_gone());
+    }
+
+    #[test]
+    fn for_each_field_skips_long_lines() {
+        let path =
+            std::env::temp_dir().join(std::format!("tty-info-fields-{}", std::process::id()));
+        let mut content = Vec::new();
+        content.extend_from_slice(b"Name:\tbash\nGroups:\t");
+        for i in 0..2000 {
+            content.extend_from_slice(std::format!("{i} ").as_bytes());
+        }
+        content.extend_from_slice(b"\nno colon\nNSpid:\t42  7\nTrailing: x");
+        std::fs::write(&path, &content).unwrap();
+
+        let cpath = CString::new(path.to_str().unwrap()).unwrap();
+        let file = File::open_at(CURRENT_DIRECTORY, &cpath).unwrap();
+        let mut fields = Vec::new();
+        file.for_each_field(|key, value| {
+            fields.push((key.to_vec(), value.to_vec()));
+            true
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            fields,
+            [
+                (b"Name".to_vec(), b"bash".to_vec()),
+                (b"NSpid".to_vec(), b"42  7".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn proc_paths() {
+        with_proc_path(Some(42), b"status", |path| {
+            assert_eq!(path.to_bytes(), b"/proc/42/status")
+        });
+        with_proc_path(None, b"ns/pid", |path| {
+            assert_eq!(path.to_bytes(), b"/proc/self/ns/pid")
+        });
+        with_pid_path(4294967295, b"stat", |path| {
+            assert_eq!(path.to_bytes(), b"4294967295/stat")
+        });
+        assert_eq!(parse_pid(c"123"), Some(123));
+        assert_eq!(parse_pid(c"self"), None);
+        assert_eq!(parse_pid(c"12a"), None);
+        assert_eq!(parse_pid(c"99999999999"), None);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{Dir, DirIterator, DirentBuf, Error, RawFd, RawProcessInfo};
+
+use super::procfs::{check_gone, parse_pid, with_pid_path, with_proc_path};
+
+/// The threads of a process, read from `/proc/<pid>/task`.
+pub struct Tasks {
+    dir: Dir,
+}
+
+impl Tasks {
+    /// Opens the threads list of the `pid` process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for which [Error::is_process_gone] is `true` if the
+    /// process does not exist.
+    pub fn for_process(pid: u32) -> Result<Self, Error> {
+        Ok(Self {
+            dir: with_proc_path(Some(pid), b"task", Dir::open)
+                .map_err(|err| check_gone(pid, err))?,
+        })
+    }
+
+    /// Opens the threads list of the current process.
+    #[inline]
+    pub fn current() -> Result<Self, Error> {
+        Ok(Self {
+            dir: Dir::open(c"/proc/self/task")?,
+        })
+    }
+
+    /// Constructs a new [TasksIterator] using `buf` as dirent buffer.
+    #[inline]
+    pub fn iter<'a, B: DirentBuf>(
+        &'a mut self,
+        buf: &'a mut B,
+    ) -> Result<TasksIterator<'a, B>, Error> {
+        let fd = self.dir.as_raw_fd();
+
+        Ok(TasksIterator {
+            fd,
+            inner: self.dir.iter(buf)?,
+        })
+    }
+}
+
+/// An iterator over the threads of a process, yielding a [RawProcessInfo]
+/// for each of them.
+///
+/// Threads exiting while iterating are skipped.
+pub struct TasksIterator<'a, B: DirentBuf> {
+    fd: RawFd,
+    inner: DirIterator<'a, B>,
+}
+
+impl<'a, B: DirentBuf> Iterator for TasksIterator<'a, B> {
+    type Item = Result<RawProcessInfo, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.inner.next()? {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err)),
+            };
+
+            let tid = match parse_pid(entry.name()) {
+                Some(tid) => tid,
+                None => continue,
+            };
+
+            match with_pid_path(tid, b"stat", |path| RawProcessInfo::parse_at(self.fd, path)) {
+                Err(err) if check_gone(tid, err).is_process_gone() => (),
+                other => return Some(other),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imp::DirBuf;
+
+    extern crate std;
+
+    #[test]
+    fn current_tasks() {
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || rx.recv());
+
+        let process = RawProcessInfo::current().unwrap();
+        let mut tasks = Tasks::current().unwrap();
+        let mut buf = DirBuf::new();
+        let mut count = 0;
+        for task in tasks.iter(&mut buf).unwrap() {
+            let task = task.unwrap();
+            assert_eq!(task.session, process.session);
+            assert_eq!(task.pgrp, process.pgrp);
+            assert_eq!(task.tty, process.tty);
+
+            let thread = RawProcessInfo::for_thread(process.pid, task.pid).unwrap();
+            assert_eq!(thread.pid, task.pid);
+            count += 1;
+        }
+        assert!(count >= 2);
+
+        tx.send(()).unwrap();
+        thread.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,478 @@
+use core::{fmt, mem::size_of};
+
+use atoi::FromRadix10Checked;
+use linux_raw_sys::{
+    general::O_CLOEXEC,
+    net::{AF_NETLINK, MSG_TRUNC, SOCK_DGRAM},
+    netlink::{sockaddr_nl, NETLINK_KOBJECT_UEVENT},
+};
+use linux_syscalls::{syscall, Sysno};
+
+use super::{with_default_paths, DirBuf, PathBuf, SearchOptions};
+use crate::{CStr, Dev, DirentBuf, Errno, Error, Operation, RawFd, TtyInfo};
+
+/// Size of the largest uevent message sent by the kernel.
+pub const UEVENT_BUFFER_SIZE: usize = 2048;
+
+/// Multicast group the kernel broadcasts uevents to.
+const KERNEL_GROUP: u32 = 1;
+
+/// The action of a [Uevent].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UeventAction {
+    /// A device was added.
+    Add,
+    /// A device was removed.
+    Remove,
+    /// A device changed.
+    Change,
+    /// A device was renamed or moved.
+    Move,
+    /// A device was brought online.
+    Online,
+    /// A device was taken offline.
+    Offline,
+    /// A driver was bound to a device.
+    Bind,
+    /// A driver was unbound from a device.
+    Unbind,
+}
+
+impl UeventAction {
+    /// Returns the action name.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::Change => "change",
+            Self::Move => "move",
+            Self::Online => "online",
+            Self::Offline => "offline",
+            Self::Bind => "bind",
+            Self::Unbind => "unbind",
+        }
+    }
+
+    fn from_bytes(buf: &[u8]) -> Option<Self> {
+        Some(match buf {
+            b"add" => Self::Add,
+            b"remove" => Self::Remove,
+            b"change" => Self::Change,
+            b"move" => Self::Move,
+            b"online" => Self::Online,
+            b"offline" => Self::Offline,
+            b"bind" => Self::Bind,
+            b"unbind" => Self::Unbind,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for UeventAction {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A uevent message, as broadcast by the kernel.
+#[derive(Debug, Clone, Copy)]
+pub struct Uevent<'a> {
+    action: UeventAction,
+    devpath: &'a CStr,
+    subsystem: Option<&'a CStr>,
+    devname: Option<&'a CStr>,
+    device: Option<Dev>,
+    env: &'a [u8],
+}
+
+impl<'a> Uevent<'a> {
+    /// Parses the uevent message in `buf`: an `ACTION@DEVPATH` header followed
+    /// by `KEY=VALUE` variables, each one terminated by a NUL byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Operation::Parse] error with [Errno::EINVAL] if `buf` is not
+    /// a kernel uevent, as the messages rebroadcast by udev, or lacks the
+    /// `ACTION` or `DEVPATH` variables.
+    pub fn parse(buf: &'a [u8]) -> Result<Self, Error> {
+        let parse_error = || Error::new(Operation::Parse, Errno::EINVAL);
+
+        let mut fields = fields(buf);
+        let header = fields.next().ok_or_else(parse_error)?;
+        if memchr::memchr(b'@', header.to_bytes()).is_none() {
+            return Err(parse_error());
+        }
+        let env = &buf[header.to_bytes_with_nul().len()..];
+
+        let (mut action, mut devpath, mut subsystem, mut devname) = (None, None, None, None);
+        let (mut major, mut minor) = (None, None);
+        for field in fields {
+            let Some((key, value)) = split_field(field) else {
+                continue;
+            };
+            match key {
+                b"ACTION" => {
+                    action =
+                        Some(UeventAction::from_bytes(value.to_bytes()).ok_or_else(parse_error)?);
+                }
+                b"DEVPATH" => devpath = Some(value),
+                b"SUBSYSTEM" => subsystem = Some(value),
+                b"DEVNAME" => devname = Some(value),
+                b"MAJOR" => major = Some(parse_u32(value).ok_or_else(parse_error)?),
+                b"MINOR" => minor = Some(parse_u32(value).ok_or_else(parse_error)?),
+                _ => (),
+            }
+        }
+
+        Ok(Self {
+            action: action.ok_or_else(parse_error)?,
+            devpath: devpath.ok_or_else(parse_error)?,
+            subsystem,
+            devname,
+            device: major
+                .zip(minor)
+                .map(|(major, minor)| Dev::from_split(major, minor)),
+            env,
+        })
+    }
+
+    /// Returns the action.
+    #[inline]
+    pub fn action(&self) -> UeventAction {
+        self.action
+    }
+
+    /// Returns the device path, relative to `/sys`.
+    #[inline]
+    pub fn devpath(&self) -> &'a CStr {
+        self.devpath
+    }
+
+    /// Returns the subsystem of the device.
+    #[inline]
+    pub fn subsystem(&self) -> Option<&'a CStr> {
+        self.subsystem
+    }
+
+    /// Returns the device node name, relative to `/dev`.
+    #[inline]
+    pub fn devname(&self) -> Option<&'a CStr> {
+        self.devname
+    }
+
+    /// Returns the device number.
+    #[inline]
+    pub fn device(&self) -> Option<Dev> {
+        self.device
+    }
+
+    /// Returns the device number and node name if the uevent is about a tty.
+    pub fn tty_device(&self) -> Option<(Dev, &'a CStr)> {
+        match self.subsystem {
+            Some(subsystem) if subsystem.to_bytes() == b"tty" => self.device.zip(self.devname),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the `key` variable.
+    pub fn get(&self, key: &[u8]) -> Option<&'a CStr> {
+        fields(self.env)
+            .filter_map(split_field)
+            .find_map(|(k, value)| (k == key).then_some(value))
+    }
+}
+
+/// Iterates over the NUL terminated strings of `buf`, ignoring an
+/// unterminated tail.
+fn fields(mut buf: &[u8]) -> impl Iterator<Item = &CStr> {
+    core::iter::from_fn(move || {
+        let end = memchr::memchr(0, buf)?;
+        let (field, rest) = buf.split_at(end + 1);
+        buf = rest;
+        Some(unsafe { CStr::from_bytes_with_nul_unchecked(field) })
+    })
+}
+
+fn split_field(field: &CStr) -> Option<(&[u8], &CStr)> {
+    let bytes = field.to_bytes_with_nul();
+    let eq = memchr::memchr(b'=', bytes)?;
+    Some((&bytes[..eq], unsafe {
+        CStr::from_bytes_with_nul_unchecked(&bytes[eq + 1..])
+    }))
+}
+
+fn parse_u32(value: &CStr) -> Option<u32> {
+    let buf = value.to_bytes();
+    match u32::from_radix_10_checked(buf) {
+        (Some(n), len) if len != 0 && len == buf.len() => Some(n),
+        _ => None,
+    }
+}
+
+/// A tty added, removed or changed, as reported by a [TtyHotplug].
+pub struct TtyHotplugEvent<'a, B: DirentBuf = PathBuf> {
+    /// The action.
+    pub action: UeventAction,
+    /// The device number of the tty.
+    pub device: Dev,
+    /// The device node name, relative to `/dev`.
+    pub name: &'a CStr,
+    /// The tty, unless it was removed or its device node was not found.
+    pub tty: Option<TtyInfo<B>>,
+}
+
+impl<B: DirentBuf> fmt::Debug for TtyHotplugEvent<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtyHotplugEvent")
+            .field("action", &self.action)
+            .field("device", &self.device)
+            .field("name", &self.name)
+            .field("tty", &self.tty)
+            .finish()
+    }
+}
+
+/// A listener of tty hotplug events, reading the uevents broadcast by the
+/// kernel on a netlink socket.
+///
+/// Receiving blocks until an event arrives; [Self::as_raw_fd] can be polled
+/// to wait for one instead.
+///
+/// The socket is automatically closed when the listener goes out of scope.
+pub struct TtyHotplug {
+    fd: RawFd,
+}
+
+impl TtyHotplug {
+    /// Opens a netlink socket subscribed to the kernel uevents.
+    pub fn open() -> Result<Self, Error> {
+        let fd = loop {
+            match unsafe {
+                syscall!(
+                    [ro] Sysno::socket,
+                    AF_NETLINK,
+                    SOCK_DGRAM | O_CLOEXEC,
+                    NETLINK_KOBJECT_UEVENT
+                )
+            } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::new(Operation::Socket, err)),
+                Ok(fd) => break fd as RawFd,
+            }
+        };
+        let this = Self { fd };
+
+        let addr = sockaddr_nl {
+            nl_family: AF_NETLINK as _,
+            nl_pad: 0,
+            nl_pid: 0,
+            nl_groups: KERNEL_GROUP,
+        };
+        match unsafe {
+            syscall!(
+                [ro] Sysno::bind,
+                fd,
+                &addr as *const sockaddr_nl,
+                size_of::<sockaddr_nl>()
+            )
+        } {
+            Err(err) => Err(Error::new(Operation::Socket, err)),
+            Ok(_) => Ok(this),
+        }
+    }
+
+    /// Extract the raw file descriptor.
+    #[inline]
+    pub const fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Receives the next tty uevent in `buf`, skipping the uevents of other
+    /// subsystems and the malformed ones.
+    ///
+    /// [UEVENT_BUFFER_SIZE] bytes are enough for any kernel uevent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Operation::Read] error with [Errno::EMSGSIZE] if a message
+    /// does not fit in `buf`, which is then discarded, and with
+    /// [Errno::ENOBUFS] if messages were lost because they were not received
+    /// fast enough. The listener can still be used after an error.
+    pub fn recv<'a>(&self, buf: &'a mut [u8]) -> Result<Uevent<'a>, Error> {
+        let len = loop {
+            let len = self.recv_from_kernel(buf)?;
+            if Uevent::parse(&buf[..len]).is_ok_and(|uevent| uevent.tty_device().is_some()) {
+                break len;
+            }
+        };
+        Uevent::parse(&buf[..len])
+    }
+
+    fn recv_from_kernel(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            let mut addr = sockaddr_nl {
+                nl_family: 0,
+                nl_pad: 0,
+                nl_pid: 0,
+                nl_groups: 0,
+            };
+            let mut addr_len = size_of::<sockaddr_nl>() as u32;
+            match unsafe {
+                syscall!(
+                    Sysno::recvfrom,
+                    self.fd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    MSG_TRUNC,
+                    &mut addr as *mut sockaddr_nl,
+                    &mut addr_len as *mut u32
+                )
+            } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::new(Operation::Read, err)),
+                Ok(len) if len > buf.len() => {
+                    return Err(Error::new(Operation::Read, Errno::EMSGSIZE))
+                }
+                // Anyone allowed to could send to the group, only trust the kernel.
+                Ok(len) if addr.nl_pid == 0 => return Ok(len),
+                Ok(_) => (),
+            }
+        }
+    }
+
+    /// Receives the next tty uevent in `buf` and looks the tty up in `dirs`,
+    /// using `dirent_buf` as dirent buffer and `path_buf` as filesystem path
+    /// buffer, unless it was removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [Self::recv] and of
+    /// [TtyInfo::by_device_with_buffers_in], except when the tty is not found.
+    pub fn next_event_with_buffers_in<'a, 'b, I, B1, B>(
+        &self,
+        buf: &'a mut [u8],
+        dirs: I,
+        dirent_buf: &mut B1,
+        path_buf: B,
+    ) -> Result<TtyHotplugEvent<'a, B>, Error>
+    where
+        I: IntoIterator<Item = &'b CStr>,
+        B1: DirentBuf,
+        B: DirentBuf,
+    {
+        let uevent = self.recv(buf)?;
+        let (device, name) = uevent
+            .tty_device()
+            .ok_or(Error::new(Operation::Parse, Errno::EINVAL))?;
+
+        let tty = if uevent.action() == UeventAction::Remove {
+            None
+        } else {
+            match TtyInfo::find_with_buffers_in(
+                device,
+                name,
+                dirs,
+                &SearchOptions::new(),
+                dirent_buf,
+                path_buf,
+            ) {
+                Err(err) if err.operation() == Operation::Lookup => None,
+                res => Some(res?),
+            }
+        };
+
+        Ok(TtyHotplugEvent {
+            action: uevent.action(),
+            device,
+            name,
+            tty,
+        })
+    }
+
+    /// Same as [Self::next_event_with_buffers_in] but with default buffers.
+    #[inline]
+    pub fn next_event_in<'a, 'b, I>(
+        &self,
+        buf: &'a mut [u8],
+        dirs: I,
+    ) -> Result<TtyHotplugEvent<'a>, Error>
+    where
+        I: IntoIterator<Item = &'b CStr>,
+    {
+        self.next_event_with_buffers_in(buf, dirs, &mut DirBuf::new(), PathBuf::new())
+    }
+
+    /// Same as [Self::next_event_with_buffers_in] but with default buffers
+    /// and dirs.
+    #[inline]
+    pub fn next_event<'a>(&self, buf: &'a mut [u8]) -> Result<TtyHotplugEvent<'a>, Error> {
+        with_default_paths(|dirs| self.next_event_in(buf, dirs))
+    }
+}
+
+impl Drop for TtyHotplug {
+    fn drop(&mut self) {
+        _ = unsafe { syscall!([ro] Sysno::close, self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADD_USB: &[u8] =
+        b"add@/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0/ttyUSB0/tty/ttyUSB0\0\
+        ACTION=add\0\
+        DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0/ttyUSB0/tty/ttyUSB0\0\
+        SUBSYSTEM=tty\0\
+        MAJOR=188\0\
+        MINOR=0\0\
+        DEVNAME=ttyUSB0\0\
+        SEQNUM=4242\0";
+
+    #[test]
+    fn parse_tty_uevent() {
+        let uevent = Uevent::parse(ADD_USB).unwrap();
+        assert_eq!(uevent.action(), UeventAction::Add);
+        assert_eq!(uevent.subsystem(), Some(c"tty"));
+        assert_eq!(
+            uevent.tty_device(),
+            Some((Dev::from_split(188, 0), c"ttyUSB0"))
+        );
+        assert_eq!(uevent.get(b"SEQNUM"), Some(c"4242"));
+        assert_eq!(uevent.get(b"DRIVER"), None);
+
+        let remove = b"remove@/devices/virtual/tty/ttyACM1\0ACTION=remove\0\
+            DEVPATH=/devices/virtual/tty/ttyACM1\0SUBSYSTEM=tty\0MAJOR=166\0MINOR=1\0\
+            DEVNAME=ttyACM1";
+        let uevent = Uevent::parse(remove).unwrap();
+        assert_eq!(uevent.action(), UeventAction::Remove);
+        assert_eq!(uevent.device(), Some(Dev::from_split(166, 1)));
+        // The unterminated tail is ignored.
+        assert_eq!(uevent.tty_device(), None);
+    }
+
+    #[test]
+    fn parse_other_uevents() {
+        let usb = b"bind@/devices/usb1/1-2\0ACTION=bind\0DEVPATH=/devices/usb1/1-2\0\
+            SUBSYSTEM=usb\0MAJOR=189\0MINOR=1\0DEVNAME=bus/usb/001/002\0";
+        let uevent = Uevent::parse(usb).unwrap();
+        assert_eq!(uevent.action(), UeventAction::Bind);
+        assert_eq!(uevent.tty_device(), None);
+
+        let invalid: [&[u8]; 5] = [
+            b"",
+            b"libudev\0\xfe\xed\xca\xfe",
+            b"add@/devices/x\0DEVPATH=/devices/x\0",
+            b"explode@/devices/x\0ACTION=explode\0DEVPATH=/devices/x\0",
+            b"add@/devices/x\0ACTION=add\0DEVPATH=/devices/x\0MAJOR=-1\0",
+        ];
+        for buf in invalid {
+            let err = Uevent::parse(buf).unwrap_err();
+            assert_eq!(err.operation(), Operation::Parse);
+            assert_eq!(err.errno(), Errno::EINVAL);
+        }
+    }
+}
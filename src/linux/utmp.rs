@@ -0,0 +1,458 @@
+use crate::{CStr, DirentBuf, Errno, Error, Operation, TtyInfo};
+use linux_stat::CURRENT_DIRECTORY;
+
+use super::procfs::File;
+
+/// Default path of the utmp file, listing current logins.
+pub const UTMP_PATH: &CStr = c"/var/run/utmp";
+/// Default path of the wtmp file, listing past logins.
+pub const WTMP_PATH: &CStr = c"/var/log/wtmp";
+
+const LINE_LEN: usize = 32;
+const ID_LEN: usize = 4;
+const USER_LEN: usize = 32;
+const HOST_LEN: usize = 256;
+const MAX_RECORD_LEN: usize = 400;
+
+/// Binary layout of utmp records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UtmpFormat {
+    /// 384 bytes records with a 32-bit `ut_tv`, used by every 32-bit target
+    /// and by glibc on 64-bit targets with a 32-bit compat mode.
+    Time32,
+    /// 400 bytes records with a native 64-bit `ut_tv`.
+    Time64,
+}
+
+impl UtmpFormat {
+    /// The format used by the C library of the current target.
+    #[cfg(any(
+        target_pointer_width = "32",
+        all(
+            target_env = "gnu",
+            any(
+                target_arch = "x86_64",
+                target_arch = "powerpc64",
+                target_arch = "s390x",
+                target_arch = "sparc64"
+            )
+        )
+    ))]
+    pub const NATIVE: Self = Self::Time32;
+    /// The format used by the C library of the current target.
+    #[cfg(not(any(
+        target_pointer_width = "32",
+        all(
+            target_env = "gnu",
+            any(
+                target_arch = "x86_64",
+                target_arch = "powerpc64",
+                target_arch = "s390x",
+                target_arch = "sparc64"
+            )
+        )
+    )))]
+    pub const NATIVE: Self = Self::Time64;
+
+    /// Returns the size of a record.
+    #[inline]
+    pub const fn record_len(&self) -> usize {
+        match self {
+            Self::Time32 => 384,
+            Self::Time64 => 400,
+        }
+    }
+}
+
+impl Default for UtmpFormat {
+    #[inline]
+    fn default() -> Self {
+        Self::NATIVE
+    }
+}
+
+/// Type of a utmp record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UtmpKind {
+    /// Not valid.
+    Empty,
+    /// Change in system run-level.
+    RunLevel,
+    /// Time of system boot.
+    BootTime,
+    /// Time after system clock change.
+    NewTime,
+    /// Time before system clock change.
+    OldTime,
+    /// Process spawned by init.
+    InitProcess,
+    /// Session leader process for user login.
+    LoginProcess,
+    /// Normal process.
+    UserProcess,
+    /// Terminated process.
+    DeadProcess,
+    /// Not implemented.
+    Accounting,
+    /// Any other value.
+    Unknown(i16),
+}
+
+impl From<i16> for UtmpKind {
+    fn from(value: i16) -> Self {
+        match value {
+            0 => Self::Empty,
+            1 => Self::RunLevel,
+            2 => Self::BootTime,
+            3 => Self::NewTime,
+            4 => Self::OldTime,
+            5 => Self::InitProcess,
+            6 => Self::LoginProcess,
+            7 => Self::UserProcess,
+            8 => Self::DeadProcess,
+            9 => Self::Accounting,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// A record of a utmp or wtmp file.
+#[derive(Clone, Copy)]
+pub struct UtmpRecord {
+    kind: UtmpKind,
+    pid: i32,
+    line: [u8; LINE_LEN],
+    id: [u8; ID_LEN],
+    user: [u8; USER_LEN],
+    host: [u8; HOST_LEN],
+    termination: i16,
+    exit: i16,
+    session: i32,
+    seconds: i64,
+    microseconds: i64,
+    addr: [u8; 16],
+}
+
+#[inline]
+fn until_nul(buf: &[u8]) -> &[u8] {
+    match memchr::memchr(0, buf) {
+        Some(i) => &buf[..i],
+        None => buf,
+    }
+}
+
+impl UtmpRecord {
+    /// Parses a record in `format` from `buf`, in native endianness.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Errno::EINVAL] if `buf` is not exactly a record long.
+    pub fn parse(buf: &[u8], format: UtmpFormat) -> Result<Self, Error> {
+        if buf.len() != format.record_len() {
+            return Err(Error::new(Operation::Parse, Errno::EINVAL));
+        }
+
+        fn array<const N: usize>(buf: &[u8], offset: usize) -> [u8; N] {
+            let mut res = [0; N];
+            res.copy_from_slice(&buf[offset..(offset + N)]);
+            res
+        }
+
+        // `ut_session` is a `long` in the 64-bit layout.
+        let (session, seconds, microseconds, addr) = match format {
+            UtmpFormat::Time32 => (
+                i32::from_ne_bytes(array(buf, 336)),
+                i32::from_ne_bytes(array(buf, 340)) as i64,
+                i32::from_ne_bytes(array(buf, 344)) as i64,
+                array(buf, 348),
+            ),
+            UtmpFormat::Time64 => (
+                i64::from_ne_bytes(array(buf, 336)) as i32,
+                i64::from_ne_bytes(array(buf, 344)),
+                i64::from_ne_bytes(array(buf, 352)),
+                array(buf, 360),
+            ),
+        };
+
+        Ok(Self {
+            kind: i16::from_ne_bytes(array(buf, 0)).into(),
+            pid: i32::from_ne_bytes(array(buf, 4)),
+            line: array(buf, 8),
+            id: array(buf, 40),
+            user: array(buf, 44),
+            host: array(buf, 76),
+            termination: i16::from_ne_bytes(array(buf, 332)),
+            exit: i16::from_ne_bytes(array(buf, 334)),
+            session,
+            seconds,
+            microseconds,
+            addr,
+        })
+    }
+
+    /// Returns the record type.
+    #[inline]
+    pub const fn kind(&self) -> UtmpKind {
+        self.kind
+    }
+
+    /// Returns the pid of the login process.
+    #[inline]
+    pub const fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// Returns the tty name, relative to `/dev`.
+    #[inline]
+    pub fn line(&self) -> &[u8] {
+        until_nul(&self.line)
+    }
+
+    /// Returns the terminal name suffix, or inittab id.
+    #[inline]
+    pub fn id(&self) -> &[u8] {
+        until_nul(&self.id)
+    }
+
+    /// Returns the user name.
+    #[inline]
+    pub fn user(&self) -> &[u8] {
+        until_nul(&self.user)
+    }
+
+    /// Returns the host name for remote logins, or the kernel version for
+    /// run-level records.
+    #[inline]
+    pub fn host(&self) -> &[u8] {
+        until_nul(&self.host)
+    }
+
+    /// Returns the termination status of a [UtmpKind::DeadProcess].
+    #[inline]
+    pub const fn termination(&self) -> i16 {
+        self.termination
+    }
+
+    /// Returns the exit status of a [UtmpKind::DeadProcess].
+    #[inline]
+    pub const fn exit(&self) -> i16 {
+        self.exit
+    }
+
+    /// Returns the session id, used for windowing.
+    #[inline]
+    pub const fn session(&self) -> i32 {
+        self.session
+    }
+
+    /// Returns the seconds since epoch of the entry.
+    #[inline]
+    pub const fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// Returns the microseconds part of the entry time.
+    #[inline]
+    pub const fn microseconds(&self) -> i64 {
+        self.microseconds
+    }
+
+    /// Returns the remote host address, in network byte order: only the
+    /// first 4 bytes are used for IPv4 addresses.
+    #[inline]
+    pub const fn addr(&self) -> [u8; 16] {
+        self.addr
+    }
+}
+
+impl core::fmt::Debug for UtmpRecord {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        struct Bytes<'a>(&'a [u8]);
+        impl<'a> core::fmt::Debug for Bytes<'a> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, "\"{}\"", self.0.escape_ascii())
+            }
+        }
+
+        f.debug_struct("UtmpRecord")
+            .field("kind", &self.kind)
+            .field("pid", &self.pid)
+            .field("line", &Bytes(self.line()))
+            .field("id", &Bytes(self.id()))
+            .field("user", &Bytes(self.user()))
+            .field("host", &Bytes(self.host()))
+            .field("termination", &self.termination)
+            .field("exit", &self.exit)
+            .field("session", &self.session)
+            .field("seconds", &self.seconds)
+            .field("microseconds", &self.microseconds)
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+/// A reader over the records of a utmp or wtmp file.
+///
+/// Yields [Errno::ENODATA] if the file ends in the middle of a record.
+pub struct Utmp {
+    file: File,
+    format: UtmpFormat,
+}
+
+impl Utmp {
+    /// Opens the utmp file at `path`, relative to current working directory.
+    #[inline]
+    pub fn open_path(path: &CStr) -> Result<Self, Error> {
+        Ok(Self {
+            file: File::open_at(CURRENT_DIRECTORY, path)?,
+            format: UtmpFormat::NATIVE,
+        })
+    }
+
+    /// Opens the utmp file at [UTMP_PATH].
+    #[inline]
+    pub fn open() -> Result<Self, Error> {
+        Self::open_path(UTMP_PATH)
+    }
+
+    /// Sets the record format, [UtmpFormat::NATIVE] by default.
+    #[inline]
+    pub fn with_format(mut self, format: UtmpFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Returns the first [UtmpKind::UserProcess] record logged in on `line`,
+    /// searching from the current position.
+    pub fn find_by_line(&mut self, line: &[u8]) -> Result<Option<UtmpRecord>, Error> {
+        for record in self {
+            let record = record?;
+            if record.kind() == UtmpKind::UserProcess && record.line() == line {
+                return Ok(Some(record));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Same as [Self::find_by_line] with the [TtyInfo::name] of `tty`.
+    #[inline]
+    pub fn find_by_tty<B: DirentBuf>(
+        &mut self,
+        tty: &TtyInfo<B>,
+    ) -> Result<Option<UtmpRecord>, Error> {
+        self.find_by_line(tty.name().to_bytes())
+    }
+}
+
+impl Iterator for Utmp {
+    type Item = Result<UtmpRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = [0u8; MAX_RECORD_LEN];
+        let buf = match self.file.read_to_fill(&mut buf[..self.format.record_len()]) {
+            Ok(buf) => buf,
+            Err(err) => return Some(Err(err)),
+        };
+
+        if buf.is_empty() {
+            None
+        } else if buf.len() != self.format.record_len() {
+            Some(Err(Error::new(Operation::Read, Errno::ENODATA)))
+        } else {
+            Some(UtmpRecord::parse(buf, self.format))
+        }
+    }
+}
+
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    /// Reads a fixture generated by `testdata/gen_utmp.py`.
+    fn fixture(name: &str) -> std::ffi::CString {
+        std::ffi::CString::new(std::format!(
+            "{}/testdata/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    fn check_fixture(utmp: Utmp) {
+        let records = utmp.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records.len(), 5);
+
+        assert_eq!(records[0].kind(), UtmpKind::BootTime);
+        assert_eq!(records[0].line(), b"~");
+        assert_eq!(records[0].host(), b"6.1.0-test");
+        assert_eq!(records[0].seconds(), 1_700_000_000);
+
+        assert_eq!(records[1].kind(), UtmpKind::LoginProcess);
+        assert_eq!(records[1].line(), b"tty1");
+        assert_eq!(records[1].user(), b"LOGIN");
+        assert_eq!(records[1].session(), 812);
+
+        let alice = records[2];
+        assert_eq!(alice.kind(), UtmpKind::UserProcess);
+        assert_eq!(alice.pid(), 4242);
+        assert_eq!(alice.line(), b"pts/0");
+        assert_eq!(alice.id(), b"ts/0");
+        assert_eq!(alice.user(), b"alice");
+        assert_eq!(alice.host(), b"192.168.1.2");
+        assert_eq!(alice.addr()[..4], [192, 168, 1, 2]);
+        assert_eq!(alice.seconds(), 1_700_000_100);
+        assert_eq!(alice.microseconds(), 123_456);
+
+        assert_eq!(records[3].kind(), UtmpKind::DeadProcess);
+        assert_eq!(records[3].line(), b"pts/1");
+        assert_eq!(records[3].exit(), 1);
+
+        assert_eq!(records[4].kind(), UtmpKind::UserProcess);
+        assert_eq!(records[4].user(), b"a-user-name-exactly-32-bytes-lon");
+        assert_eq!(records[4].host(), b"");
+        assert_eq!(records[4].session(), 5000);
+    }
+
+    #[test]
+    fn read_time32_fixture() {
+        check_fixture(
+            Utmp::open_path(&fixture("utmp32"))
+                .unwrap()
+                .with_format(UtmpFormat::Time32),
+        );
+    }
+
+    #[test]
+    fn read_time64_fixture() {
+        check_fixture(
+            Utmp::open_path(&fixture("utmp64"))
+                .unwrap()
+                .with_format(UtmpFormat::Time64),
+        );
+    }
+
+    #[test]
+    fn find_by_line() {
+        let mut utmp = Utmp::open_path(&fixture("utmp32"))
+            .unwrap()
+            .with_format(UtmpFormat::Time32);
+        assert_eq!(utmp.find_by_line(b"pts/0").unwrap().unwrap().pid(), 4242);
+
+        let mut utmp = Utmp::open_path(&fixture("utmp32"))
+            .unwrap()
+            .with_format(UtmpFormat::Time32);
+        assert!(utmp.find_by_line(b"pts/1").unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_record() {
+        let mut utmp = Utmp::open_path(&fixture("utmp32"))
+            .unwrap()
+            .with_format(UtmpFormat::Time64);
+        let err = utmp.find(|r| r.is_err()).unwrap().unwrap_err();
+        assert_eq!(err.errno(), Errno::ENODATA);
+    }
+}
@@ -0,0 +1,550 @@
+use core::{cell::Cell, ops::ControlFlow};
+
+use linux_stat::{fstatat_cstr, StatAtFlags};
+
+use super::{dir::Cursor, push_path};
+use crate::{CStr, Dev, Dir, DirentBuf, DirentFileType, Errno, Error, Operation, Resolve, Stat};
+
+/// Space kept for a subdirectory after the entries of its parent.
+const MIN_CHILD_BUF_LEN: usize = 1024;
+
+/// Default depth of the deepest subdirectories a [Walker] may walk.
+pub const DEFAULT_DEPTH_LIMIT: usize = 32;
+
+/// How a [Walker] handles symbolic links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SymlinkPolicy {
+    /// Reports links as links, without following them.
+    #[default]
+    NoFollow,
+    /// Reports links as their target and descends into linked directories.
+    Follow,
+}
+
+/// An entry visited by a [Walker].
+pub struct WalkEntry<'a> {
+    dir: &'a Dir,
+    name: &'a CStr,
+    path: &'a CStr,
+    depth: usize,
+    file_type: DirentFileType,
+    follow: bool,
+    stat: Cell<Option<Stat>>,
+}
+
+impl<'a> WalkEntry<'a> {
+    /// Returns the file name of the entry.
+    #[inline]
+    pub fn name(&self) -> &'a CStr {
+        self.name
+    }
+
+    /// Returns the directory holding the entry.
+    #[inline]
+    pub fn dir(&self) -> &'a Dir {
+        self.dir
+    }
+
+    /// Returns the path of the entry: the path of the walked directory
+    /// followed by the names leading to the entry.
+    #[inline]
+    pub fn path(&self) -> &'a CStr {
+        self.path
+    }
+
+    /// Returns the depth of the entry, `0` for the entries of the walked
+    /// directory.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the file type of the entry, from its metadata when the
+    /// directory does not report it or when following links.
+    #[inline]
+    pub fn file_type(&self) -> DirentFileType {
+        self.file_type
+    }
+
+    /// Returns the metadata of the entry, read on the first call.
+    pub fn metadata(&self) -> Result<Stat, Error> {
+        if let Some(md) = self.stat.get() {
+            return Ok(md);
+        }
+
+        let flags = if self.follow {
+            StatAtFlags::empty()
+        } else {
+            StatAtFlags::SYMLINK_NOFOLLOW
+        };
+        loop {
+            match unsafe { fstatat_cstr(self.dir.as_raw_fd(), self.name, flags) } {
+                Err(Errno::EINTR) => (),
+                Err(err) => {
+                    return Err(Error::with_path(
+                        Operation::Fstatat,
+                        self.path.to_bytes(),
+                        err,
+                    ))
+                }
+                Ok(md) => {
+                    self.stat.set(Some(md));
+                    return Ok(md);
+                }
+            }
+        }
+    }
+}
+
+/// A directory being walked and the ones it is in, to detect loops.
+struct Ancestor<'a> {
+    dev: Dev,
+    ino: u64,
+    parent: Option<&'a Ancestor<'a>>,
+}
+
+impl Ancestor<'_> {
+    fn contains(&self, dev: Dev, ino: u64) -> bool {
+        let mut ancestor = Some(self);
+        while let Some(dir) = ancestor {
+            if dir.dev == dev && dir.ino == ino {
+                return true;
+            }
+            ancestor = dir.parent;
+        }
+        false
+    }
+}
+
+/// A recursive directory walker.
+///
+/// The walk reads all the directories in a single dirent buffer: the entries
+/// of a subdirectory are read after those of its parent, which is read again
+/// from where it stopped only if the buffer cannot grow.
+///
+/// Walks fail with an [Operation::Walk] error instead of descending in a
+/// directory which contains itself, with [Errno::ELOOP], or deeper than the
+/// depth limit, with [Errno::EOVERFLOW].
+pub struct Walker<'a, B: DirentBuf, P = fn(&WalkEntry<'_>) -> bool> {
+    buf: &'a mut B,
+    max_depth: usize,
+    depth_limit: usize,
+    same_filesystem: bool,
+    symlinks: SymlinkPolicy,
+    resolve: Resolve,
+    max_buffer_len: usize,
+    prune: P,
+}
+
+impl<'a, B: DirentBuf> Walker<'a, B> {
+    /// Creates a walker using `buf` as dirent buffer, descending in all
+    /// subdirectories up to [DEFAULT_DEPTH_LIMIT], across filesystems, but
+    /// without following links.
+    #[inline]
+    pub fn new(buf: &'a mut B) -> Self {
+        Self {
+            buf,
+            max_depth: usize::MAX,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+            same_filesystem: false,
+            symlinks: SymlinkPolicy::NoFollow,
+            resolve: Resolve::Any,
+            max_buffer_len: usize::MAX,
+            prune: |_| false,
+        }
+    }
+}
+
+impl<'a, B, P> Walker<'a, B, P>
+where
+    B: DirentBuf,
+    P: FnMut(&WalkEntry<'_>) -> bool,
+{
+    /// Sets the depth of the deepest subdirectories to descend in, `0` to
+    /// only visit the entries of the walked directory.
+    #[inline]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the depth of the deepest subdirectories the walk may reach before
+    /// failing.
+    #[inline]
+    pub fn depth_limit(mut self, depth_limit: usize) -> Self {
+        self.depth_limit = depth_limit;
+        self
+    }
+
+    /// Sets whether to skip the subdirectories on another filesystem than
+    /// the walked directory, as mount points.
+    #[inline]
+    pub fn same_filesystem(mut self, same_filesystem: bool) -> Self {
+        self.same_filesystem = same_filesystem;
+        self
+    }
+
+    /// Sets how symbolic links are handled.
+    #[inline]
+    pub fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets how the subdirectories are opened, relative to the directory
+    /// holding them.
+    #[inline]
+    pub fn resolve(mut self, resolve: Resolve) -> Self {
+        self.resolve = resolve;
+        self
+    }
+
+    /// Sets the length the dirent buffer may grow to when an entry does not
+    /// fit, failing with [Errno::ENOMEM] beyond. Unbounded by default.
+    #[inline]
+    pub fn max_buffer_len(mut self, len: usize) -> Self {
+        self.max_buffer_len = len;
+        self
+    }
+
+    /// Sets the predicate of the subdirectories to skip.
+    #[inline]
+    pub fn prune<P2>(self, prune: P2) -> Walker<'a, B, P2>
+    where
+        P2: FnMut(&WalkEntry<'_>) -> bool,
+    {
+        Walker {
+            buf: self.buf,
+            max_depth: self.max_depth,
+            depth_limit: self.depth_limit,
+            same_filesystem: self.same_filesystem,
+            symlinks: self.symlinks,
+            resolve: self.resolve,
+            max_buffer_len: self.max_buffer_len,
+            prune,
+        }
+    }
+
+    /// Walks `dir`, whose path is in `path`, calling `visit` on each entry
+    /// before descending in it until it breaks.
+    ///
+    /// Returns the value `visit` broke with, if any. `path` then holds the
+    /// NUL terminated path of the entry it broke on; otherwise it is left
+    /// unchanged. Entries removed while walking are skipped.
+    pub fn walk<B2, V, T>(
+        &mut self,
+        dir: &mut Dir,
+        path: &mut B2,
+        mut visit: V,
+    ) -> Result<Option<T>, Error>
+    where
+        B2: DirentBuf,
+        V: FnMut(&WalkEntry<'_>) -> Result<ControlFlow<T>, Error>,
+    {
+        let md = dir_metadata(dir).map_err(|err| err.or_path(path))?;
+        let root = Ancestor {
+            dev: md.dev(),
+            ino: md.inode(),
+            parent: None,
+        };
+        let cursor = Cursor::resuming(dir).max_buffer_len(self.max_buffer_len);
+        self.walk_dir(dir, cursor, &root, 0, path, &mut visit)
+    }
+
+    fn walk_dir<B2, V, T>(
+        &mut self,
+        dir: &mut Dir,
+        mut cursor: Cursor,
+        ancestors: &Ancestor<'_>,
+        depth: usize,
+        path: &mut B2,
+        visit: &mut V,
+    ) -> Result<Option<T>, Error>
+    where
+        B2: DirentBuf,
+        V: FnMut(&WalkEntry<'_>) -> Result<ControlFlow<T>, Error>,
+    {
+        let dir_len = path.len();
+
+        loop {
+            let subdir = {
+                let entry = match cursor.next(dir, self.buf) {
+                    None => return Ok(None),
+                    Some(entry) => entry.map_err(|err| err.in_dir(&path[..dir_len]))?,
+                };
+                let name = entry.name();
+                if matches!(name.to_bytes(), b"." | b"..") {
+                    continue;
+                }
+
+                push_path(path, &[b"/", name.to_bytes(), b"\0"])?;
+                let mut entry = WalkEntry {
+                    dir: &*dir,
+                    name,
+                    path: unsafe { CStr::from_bytes_with_nul_unchecked(path.as_slice()) },
+                    depth,
+                    file_type: entry.file_type(),
+                    follow: self.symlinks == SymlinkPolicy::Follow,
+                    stat: Cell::new(None),
+                };
+                let resolve = match entry.file_type {
+                    DirentFileType::Unknown => true,
+                    DirentFileType::Link => entry.follow,
+                    _ => false,
+                };
+                if resolve {
+                    match entry.metadata() {
+                        Ok(md) => entry.file_type = md.file_type().into(),
+                        // Dangling link.
+                        Err(err) if err.errno() == Errno::ENOENT && entry.follow => (),
+                        // Removed since the directory was read.
+                        Err(err) if err.errno() == Errno::ENOENT => {
+                            unsafe { path.set_len(dir_len) };
+                            continue;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+
+                if let ControlFlow::Break(value) = visit(&entry)? {
+                    return Ok(Some(value));
+                }
+
+                if entry.file_type == DirentFileType::Directory
+                    && depth < self.max_depth
+                    && !(self.prune)(&entry)
+                {
+                    match Dir::open_at_with(dir, name, self.resolve) {
+                        Err(err) if err.errno() == Errno::ENOENT => None,
+                        Err(err) => return Err(err.in_dir(&path[..dir_len])),
+                        Ok(subdir) => Some(subdir),
+                    }
+                } else {
+                    None
+                }
+            };
+
+            if let Some(mut subdir) = subdir {
+                // Drops the NUL terminator.
+                unsafe { path.set_len(path.len() - 1) };
+
+                let md = dir_metadata(&subdir).map_err(|err| err.or_path(path))?;
+                if self.same_filesystem && md.dev() != ancestors.dev {
+                    unsafe { path.set_len(dir_len) };
+                    continue;
+                }
+                if ancestors.contains(md.dev(), md.inode()) {
+                    return Err(Error::with_path(Operation::Walk, path, Errno::ELOOP));
+                }
+                if depth + 1 > self.depth_limit {
+                    return Err(Error::with_path(Operation::Walk, path, Errno::EOVERFLOW));
+                }
+                let ancestor = Ancestor {
+                    dev: md.dev(),
+                    ino: md.inode(),
+                    parent: Some(ancestors),
+                };
+
+                let child_len = cursor.end() + MIN_CHILD_BUF_LEN;
+                let base =
+                    if child_len <= self.max_buffer_len && self.buf.reserve(child_len).is_ok() {
+                        cursor.end()
+                    } else {
+                        cursor.invalidate();
+                        cursor.base()
+                    };
+                let cursor = Cursor::new(base).max_buffer_len(self.max_buffer_len);
+                if let Some(value) =
+                    self.walk_dir(&mut subdir, cursor, &ancestor, depth + 1, path, visit)?
+                {
+                    return Ok(Some(value));
+                }
+            }
+            unsafe { path.set_len(dir_len) };
+        }
+    }
+}
+
+/// Reads the metadata of the open directory `dir`.
+fn dir_metadata(dir: &Dir) -> Result<Stat, Error> {
+    loop {
+        match unsafe { fstatat_cstr(dir.as_raw_fd(), c"", StatAtFlags::EMPTY_PATH) } {
+            Err(Errno::EINTR) => (),
+            Err(err) => return Err(Error::new(Operation::Fstatat, err)),
+            Ok(md) => return Ok(md),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{imp::DirBuf, ArrayBuffer};
+
+    extern crate std;
+    use std::{collections::BTreeSet, ffi::CString, format, path::PathBuf, string::String};
+
+    /// Creates a tree with files, nested and pruned directories and a link
+    /// to a directory.
+    fn make_tree(tag: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("tty-info-walk-{tag}-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sub/deep")).unwrap();
+        std::fs::create_dir(root.join("skip")).unwrap();
+        std::fs::write(root.join("a"), b"").unwrap();
+        std::fs::write(root.join("sub/deep/c"), b"").unwrap();
+        std::fs::write(root.join("skip/x"), b"").unwrap();
+        for i in 0..40 {
+            std::fs::write(root.join(format!("sub/file-with-a-long-name-{i:02}")), b"").unwrap();
+        }
+        std::os::unix::fs::symlink("sub", root.join("link")).unwrap();
+        root
+    }
+
+    fn walk_paths<B, P>(walker: &mut Walker<'_, B, P>, root: &std::path::Path) -> BTreeSet<String>
+    where
+        B: DirentBuf,
+        P: FnMut(&WalkEntry<'_>) -> bool,
+    {
+        let root = CString::new(root.to_str().unwrap()).unwrap();
+        let mut dir = Dir::open(&root).unwrap();
+        let mut path = DirBuf::new();
+        path.push_c_str(&root).unwrap();
+
+        let mut paths = BTreeSet::new();
+        let res = walker.walk(&mut dir, &mut path, |entry| {
+            let path = entry.path().to_str().unwrap();
+            let name = &path[root.to_bytes().len() + 1..];
+            assert_eq!(entry.depth(), name.matches('/').count());
+            if !name.contains("long-name") {
+                paths.insert(format!("{name}:{:?}", entry.file_type()));
+            }
+            Ok(ControlFlow::<()>::Continue(()))
+        });
+        assert_eq!(res.unwrap(), None);
+        assert_eq!(&*path, root.to_bytes());
+        paths
+    }
+
+    #[test]
+    fn walk_tree() {
+        let root = make_tree("tree");
+        let expected = [
+            "a:Regular",
+            "link:Link",
+            "skip:Directory",
+            "skip/x:Regular",
+            "sub:Directory",
+            "sub/deep:Directory",
+            "sub/deep/c:Regular",
+        ];
+
+        let paths = walk_paths(&mut Walker::new(&mut DirBuf::new()), &root);
+        assert_eq!(paths, expected.into_iter().map(String::from).collect());
+
+        // Too small to keep the entries of the parents while reading `sub`.
+        let mut buf = ArrayBuffer::<1024>::new();
+        let paths = walk_paths(&mut Walker::new(&mut buf), &root);
+        assert_eq!(paths, expected.into_iter().map(String::from).collect());
+        // Same, through the growth cap.
+        let mut buf = DirBuf::new();
+        let paths = walk_paths(&mut Walker::new(&mut buf).max_buffer_len(1024), &root);
+        assert_eq!(paths, expected.into_iter().map(String::from).collect());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn walk_options() {
+        let root = make_tree("options");
+
+        let mut buf = DirBuf::new();
+        let mut walker = Walker::new(&mut buf)
+            .max_depth(1)
+            .symlinks(SymlinkPolicy::Follow)
+            .prune(|entry| entry.name() == c"skip");
+        let paths = walk_paths(&mut walker, &root);
+        let expected = [
+            "a:Regular",
+            "link:Directory",
+            "link/deep:Directory",
+            "skip:Directory",
+            "sub:Directory",
+            "sub/deep:Directory",
+        ];
+        assert_eq!(paths, expected.into_iter().map(String::from).collect());
+
+        let path = CString::new(root.to_str().unwrap()).unwrap();
+        let mut dir = Dir::open(&path).unwrap();
+        let mut path_buf = DirBuf::new();
+        path_buf.push_c_str(&path).unwrap();
+        let found = Walker::new(&mut buf)
+            .walk(&mut dir, &mut path_buf, |entry| {
+                if entry.name() == c"c" {
+                    Ok(ControlFlow::Break(entry.metadata()?.size()))
+                } else {
+                    Ok(ControlFlow::Continue(()))
+                }
+            })
+            .unwrap();
+        assert_eq!(found, Some(0));
+        let expected = format!("{}/sub/deep/c\0", root.to_str().unwrap());
+        assert_eq!(&*path_buf, expected.as_bytes());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn walk_limits() {
+        let root = make_tree("limits");
+        std::os::unix::fs::symlink("..", root.join("sub/deep/up")).unwrap();
+        let path = CString::new(root.to_str().unwrap()).unwrap();
+        let mut buf = DirBuf::new();
+        let walk = |walker: Walker<'_, DirBuf>| {
+            let mut dir = Dir::open(&path).unwrap();
+            let mut path_buf = DirBuf::new();
+            path_buf.push_c_str(&path).unwrap();
+            walker
+                .prune(|entry| entry.name() == c"link")
+                .walk(&mut dir, &mut path_buf, |_| {
+                    Ok(ControlFlow::<()>::Continue(()))
+                })
+        };
+
+        let err = walk(Walker::new(&mut buf).symlinks(SymlinkPolicy::Follow)).unwrap_err();
+        assert_eq!(err.operation(), Operation::Walk);
+        assert_eq!(err.errno(), Errno::ELOOP);
+        assert!(err.path().unwrap().ends_with(b"/sub/deep/up"));
+
+        let err = walk(Walker::new(&mut buf).depth_limit(1)).unwrap_err();
+        assert_eq!(err.operation(), Operation::Walk);
+        assert_eq!(err.errno(), Errno::EOVERFLOW);
+        assert!(err.path().unwrap().ends_with(b"/sub/deep"));
+
+        assert_eq!(walk(Walker::new(&mut buf).depth_limit(2)).unwrap(), None);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // `/dev/pts` is usually a mount point of its own.
+        let mut dir = Dir::open(c"/dev").unwrap();
+        let mut path_buf = DirBuf::new();
+        path_buf.push_c_str(c"/dev").unwrap();
+        let mut in_pts = false;
+        Walker::new(&mut buf)
+            .max_depth(1)
+            .same_filesystem(true)
+            .prune(|entry| entry.name() != c"pts")
+            .walk(&mut dir, &mut path_buf, |entry| {
+                in_pts |= entry.path().to_bytes().starts_with(b"/dev/pts/");
+                Ok(ControlFlow::<()>::Continue(()))
+            })
+            .unwrap();
+        let pts = std::fs::metadata("/dev/pts");
+        let dev = std::fs::metadata("/dev");
+        if let (Ok(pts), Ok(dev)) = (pts, dev) {
+            use std::os::unix::fs::MetadataExt;
+            if pts.dev() != dev.dev() {
+                assert!(!in_pts);
+            }
+        }
+    }
+}
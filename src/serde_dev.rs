@@ -0,0 +1,125 @@
+//! Serde support for [Dev], serialized as its major and minor numbers.
+//!
+//! To be used with `#[serde(with = "tty_info::serde_dev")]`, or
+//! `#[serde(with = "tty_info::serde_dev::option")]` for `Option<Dev>`.
+
+use core::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{dev_from_parts, dev_parts, CStr, Dev};
+
+/// The serialized form of a [Dev].
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Dev")]
+pub(crate) struct Parts {
+    major: u32,
+    minor: u32,
+}
+
+impl From<Dev> for Parts {
+    #[inline]
+    fn from(value: Dev) -> Self {
+        let (major, minor) = dev_parts(value);
+        Self { major, minor }
+    }
+}
+
+impl From<Parts> for Dev {
+    #[inline]
+    fn from(value: Parts) -> Self {
+        dev_from_parts(value.major, value.minor)
+    }
+}
+
+/// Serializes `dev` as a `{ major, minor }` struct.
+#[inline]
+pub fn serialize<S: Serializer>(dev: &Dev, serializer: S) -> Result<S::Ok, S::Error> {
+    Parts::from(*dev).serialize(serializer)
+}
+
+/// Deserializes a [Dev] from a `{ major, minor }` struct.
+#[inline]
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Dev, D::Error> {
+    Parts::deserialize(deserializer).map(Into::into)
+}
+
+/// Same as the parent module for `Option<Dev>`.
+pub mod option {
+    use super::*;
+
+    /// Serializes `dev` as an optional `{ major, minor }` struct.
+    #[inline]
+    pub fn serialize<S: Serializer>(dev: &Option<Dev>, serializer: S) -> Result<S::Ok, S::Error> {
+        dev.map(Parts::from).serialize(serializer)
+    }
+
+    /// Deserializes an optional [Dev] from a `{ major, minor }` struct.
+    #[inline]
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Dev>, D::Error> {
+        Option::<Parts>::deserialize(deserializer).map(|parts| parts.map(Into::into))
+    }
+}
+
+/// Serializes a [CStr] as a string, replacing invalid UTF-8 sequences with
+/// `U+FFFD`.
+pub(crate) struct LossyStr<'a>(pub &'a CStr);
+
+impl fmt::Display for LossyStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.to_bytes().utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                f.write_str("\u{fffd}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for LossyStr<'_> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Devices {
+        #[serde(with = "crate::serde_dev")]
+        dev: Dev,
+        #[serde(with = "crate::serde_dev::option")]
+        tty: Option<Dev>,
+    }
+
+    #[test]
+    fn dev_round_trip() {
+        let devices = Devices {
+            dev: dev_from_parts(136, 3),
+            tty: None,
+        };
+        let json = serde_json::to_string(&devices).unwrap();
+        assert_eq!(json, r#"{"dev":{"major":136,"minor":3},"tty":null}"#);
+        assert_eq!(serde_json::from_str::<Devices>(&json).unwrap(), devices);
+
+        let devices: Devices =
+            serde_json::from_str(r#"{"dev":{"major":4,"minor":1},"tty":{"major":4,"minor":64}}"#)
+                .unwrap();
+        assert_eq!(dev_parts(devices.dev), (4, 1));
+        assert_eq!(devices.tty.map(dev_parts), Some((4, 64)));
+    }
+
+    #[test]
+    fn lossy_str() {
+        let json = serde_json::to_string(&LossyStr(c"/dev/tty\xff1")).unwrap();
+        assert_eq!(json, "\"/dev/tty\u{fffd}1\"");
+    }
+}
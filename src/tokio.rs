@@ -0,0 +1,504 @@
+//! Asynchronous lookups and tty events for the tokio runtime.
+//!
+//! Lookups may scan a whole directory tree, so they run on the blocking
+//! thread pool of the runtime. [TtyEvents] watches `/dev` with inotify and
+//! reports the ttys being added and removed.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::CString,
+    future::poll_fn,
+    io, mem,
+    ops::ControlFlow,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use ::tokio::io::unix::AsyncFd;
+use futures_core::Stream;
+use linux_raw_sys::general::{
+    inotify_event, IN_CLOEXEC, IN_CREATE, IN_DELETE, IN_MOVED_FROM, IN_MOVED_TO, IN_NONBLOCK,
+    IN_ONLYDIR, IN_Q_OVERFLOW,
+};
+use linux_stat::{fstatat_cstr, StatAtFlags, CURRENT_DIRECTORY};
+use linux_syscalls::{syscall, Sysno};
+
+use crate::{
+    imp::{is_tty_device, with_default_paths, DirBuf, PathBuf},
+    CStr, Dev, Errno, Error, Operation, SearchOptions, TtyInfo,
+};
+
+/// Runs `f` on the blocking thread pool.
+async fn spawn_blocking<T, F>(f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    match ::tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(_) => Err(Error::new(Operation::Lookup, Errno::ECANCELED)),
+    }
+}
+
+/// Async version of [TtyInfo::current].
+pub async fn current() -> Result<Option<TtyInfo>, Error> {
+    spawn_blocking(TtyInfo::current).await
+}
+
+/// Async version of [TtyInfo::current_in].
+pub async fn current_in(dirs: Vec<CString>) -> Result<Option<TtyInfo>, Error> {
+    spawn_blocking(move || TtyInfo::current_in(dirs.iter().map(CString::as_c_str))).await
+}
+
+/// Async version of [TtyInfo::for_process].
+pub async fn for_process(pid: u32) -> Result<Option<TtyInfo>, Error> {
+    spawn_blocking(move || TtyInfo::for_process(pid)).await
+}
+
+/// Async version of [TtyInfo::for_process_in].
+pub async fn for_process_in(pid: u32, dirs: Vec<CString>) -> Result<Option<TtyInfo>, Error> {
+    spawn_blocking(move || TtyInfo::for_process_in(pid, dirs.iter().map(CString::as_c_str))).await
+}
+
+/// Async version of [TtyInfo::by_device].
+pub async fn by_device(rdev: Dev) -> Result<TtyInfo, Error> {
+    spawn_blocking(move || TtyInfo::by_device(rdev)).await
+}
+
+/// Async version of [TtyInfo::by_device_in].
+pub async fn by_device_in(rdev: Dev, dirs: Vec<CString>) -> Result<TtyInfo, Error> {
+    spawn_blocking(move || TtyInfo::by_device_in(rdev, dirs.iter().map(CString::as_c_str))).await
+}
+
+/// Finds the ttys of all `devices` in a single blocking task, sharing the
+/// same dirent buffer.
+///
+/// Returns the result of each lookup, in the order of `devices`, or an error
+/// if the task was cancelled.
+pub async fn by_devices<I>(devices: I) -> Result<Vec<Result<TtyInfo, Error>>, Error>
+where
+    I: IntoIterator<Item = Dev>,
+{
+    let devices = devices.into_iter().collect::<Vec<_>>();
+    spawn_blocking(move || Ok(with_default_paths(|dirs| lookup_all(&devices, &dirs)))).await
+}
+
+/// Same as [by_devices] looking for the ttys in `dirs`.
+pub async fn by_devices_in<I>(
+    devices: I,
+    dirs: Vec<CString>,
+) -> Result<Vec<Result<TtyInfo, Error>>, Error>
+where
+    I: IntoIterator<Item = Dev>,
+{
+    let devices = devices.into_iter().collect::<Vec<_>>();
+    spawn_blocking(move || {
+        let dirs = dirs.iter().map(CString::as_c_str).collect::<Vec<_>>();
+        Ok(lookup_all(&devices, &dirs))
+    })
+    .await
+}
+
+fn lookup_all(devices: &[Dev], dirs: &[&CStr]) -> Vec<Result<TtyInfo, Error>> {
+    let mut buf = DirBuf::new();
+    devices
+        .iter()
+        .map(|&rdev| {
+            TtyInfo::by_device_with_buffers_in(rdev, dirs.iter().copied(), &mut buf, PathBuf::new())
+        })
+        .collect()
+}
+
+/// A change of the ttys reported by [TtyEvents].
+#[derive(Debug)]
+pub enum TtyEvent {
+    /// A tty device node was created.
+    Added(Box<TtyInfo>),
+    /// A tty device node was removed.
+    Removed {
+        /// The device number of the removed tty.
+        device: Dev,
+        /// The full path of the removed device node.
+        path: CString,
+    },
+}
+
+/// A [Stream] of the ttys added to and removed from a directory and its
+/// `pts` subdirectory.
+///
+/// Device nodes are expected to be created by the kernel or `udev`, renames
+/// are reported as a removal followed by an addition. If the kernel queue
+/// overflows the directories are read again and the differences reported.
+pub struct TtyEvents {
+    fd: AsyncFd<OwnedFd>,
+    root: CString,
+    /// Prefix of the names of each watch descriptor, relative to `root`.
+    watches: HashMap<i32, Vec<u8>>,
+    /// Devices of the known ttys by name.
+    known: HashMap<Vec<u8>, Dev>,
+    pending: VecDeque<Result<TtyEvent, Error>>,
+    buf: Vec<u8>,
+}
+
+impl TtyEvents {
+    /// Watches `/dev` and `/dev/pts`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime with IO enabled.
+    #[inline]
+    pub fn new() -> Result<Self, Error> {
+        Self::in_dir(c"/dev")
+    }
+
+    /// Watches `dir` and `dir/pts`, if present.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime with IO enabled.
+    pub fn in_dir(dir: &CStr) -> Result<Self, Error> {
+        let fd = loop {
+            match unsafe { syscall!(Sysno::inotify_init1, IN_NONBLOCK | IN_CLOEXEC) } {
+                Err(Errno::EINTR) => (),
+                Err(err) => return Err(Error::new(Operation::Inotify, err)),
+                Ok(fd) => break unsafe { OwnedFd::from_raw_fd(fd as RawFd) },
+            }
+        };
+
+        let mut watches = HashMap::new();
+        for prefix in [&b""[..], b"pts/"] {
+            let path = join(dir, prefix);
+            let mask = IN_CREATE | IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO | IN_ONLYDIR;
+            match unsafe {
+                syscall!(
+                    Sysno::inotify_add_watch,
+                    fd.as_raw_fd(),
+                    path.as_ptr(),
+                    mask
+                )
+            } {
+                Err(Errno::ENOENT | Errno::ENOTDIR) if !prefix.is_empty() => (),
+                Err(err) => {
+                    return Err(Error::with_path(Operation::Inotify, path.to_bytes(), err));
+                }
+                Ok(wd) => {
+                    watches.insert(wd as i32, prefix.to_vec());
+                }
+            }
+        }
+
+        let mut this = Self {
+            fd: AsyncFd::new(fd).map_err(|err| io_error(Operation::Inotify, err))?,
+            root: dir.into(),
+            watches,
+            known: HashMap::new(),
+            pending: VecDeque::new(),
+            buf: vec![0; 4096],
+        };
+        for (name, tty) in this.scan()? {
+            this.known.insert(name, tty.device());
+        }
+        Ok(this)
+    }
+
+    /// Waits for the next event.
+    pub async fn next_event(&mut self) -> Result<TtyEvent, Error> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+            .await
+            .unwrap_or_else(|| unreachable!("TtyEvents never ends"))
+    }
+
+    /// Returns the tty at `name`, relative to the root, if there is one.
+    fn tty_at(&self, name: &[u8]) -> Result<Option<TtyInfo>, Error> {
+        let path = join(&self.root, name);
+        let md = loop {
+            match unsafe { fstatat_cstr(CURRENT_DIRECTORY, &path, StatAtFlags::SYMLINK_NOFOLLOW) } {
+                Err(Errno::EINTR) => (),
+                Err(Errno::ENOENT) => return Ok(None),
+                Err(err) => {
+                    return Err(Error::with_path(Operation::Fstatat, path.to_bytes(), err));
+                }
+                Ok(md) => break md,
+            }
+        };
+
+        if md.is_char() && is_tty_device(md.rdev()) {
+            TtyInfo::at(&self.root, name, md).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the ttys of the root directory and of its `pts` subdirectory.
+    fn scan(&self) -> Result<Vec<(Vec<u8>, TtyInfo)>, Error> {
+        let mut ttys = Vec::new();
+        TtyInfo::enumerate_in([self.root.as_c_str()], &SearchOptions::new(), |tty| {
+            ttys.push((tty.name().to_bytes().to_vec(), tty));
+            ControlFlow::<()>::Continue(())
+        })?;
+
+        Ok(ttys)
+    }
+
+    /// Reads the watched directories again, queueing the differences with
+    /// the known ttys.
+    fn rescan(&mut self) {
+        let ttys = match self.scan() {
+            Ok(ttys) => ttys,
+            Err(err) => return self.pending.push_back(Err(err)),
+        };
+
+        let mut known = mem::take(&mut self.known);
+        for (name, tty) in ttys {
+            let device = tty.device();
+            match known.remove(&name) {
+                Some(dev) if dev == device => (),
+                old => {
+                    if let Some(dev) = old {
+                        self.removed(&name, dev);
+                    }
+                    self.pending.push_back(Ok(TtyEvent::Added(Box::new(tty))));
+                }
+            }
+            self.known.insert(name, device);
+        }
+        for (name, dev) in known {
+            self.removed(&name, dev);
+        }
+    }
+
+    fn removed(&mut self, name: &[u8], device: Dev) {
+        let path = join(&self.root, name);
+        self.pending
+            .push_back(Ok(TtyEvent::Removed { device, path }));
+    }
+
+    /// Queues the events of an inotify read.
+    fn handle(&mut self, mut events: &[u8]) {
+        const HEADER_LEN: usize = mem::size_of::<inotify_event>();
+
+        while events.len() >= HEADER_LEN {
+            let event = unsafe { events.as_ptr().cast::<inotify_event>().read_unaligned() };
+            let len = HEADER_LEN + event.len as usize;
+            let name = events.get(HEADER_LEN..len).unwrap_or_default();
+            let name = &name[..memchr::memchr(0, name).unwrap_or(name.len())];
+            events = events.get(len..).unwrap_or_default();
+
+            if event.mask & IN_Q_OVERFLOW != 0 {
+                self.rescan();
+                continue;
+            }
+
+            let name = match self.watches.get(&event.wd) {
+                Some(prefix) => [prefix.as_slice(), name].concat(),
+                None => continue,
+            };
+
+            if event.mask & (IN_CREATE | IN_MOVED_TO) != 0 {
+                match self.tty_at(&name) {
+                    Ok(Some(tty)) => {
+                        if let Some(dev) = self.known.insert(name.clone(), tty.device()) {
+                            self.removed(&name, dev);
+                        }
+                        self.pending.push_back(Ok(TtyEvent::Added(Box::new(tty))));
+                    }
+                    Ok(None) => (),
+                    Err(err) => self.pending.push_back(Err(err)),
+                }
+            } else if event.mask & (IN_DELETE | IN_MOVED_FROM) != 0 {
+                if let Some(dev) = self.known.remove(&name) {
+                    self.removed(&name, dev);
+                }
+            }
+        }
+    }
+}
+
+impl Stream for TtyEvents {
+    type Item = Result<TtyEvent, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+
+            let res = {
+                let mut guard = match ready!(this.fd.poll_read_ready(cx)) {
+                    Ok(guard) => guard,
+                    Err(err) => return Poll::Ready(Some(Err(io_error(Operation::Read, err)))),
+                };
+                let buf = &mut this.buf;
+                match guard.try_io(|fd| read(fd.as_raw_fd(), buf)) {
+                    Ok(res) => res,
+                    Err(_would_block) => continue,
+                }
+            };
+
+            match res {
+                Ok(len) => {
+                    let buf = mem::take(&mut this.buf);
+                    this.handle(&buf[..len]);
+                    this.buf = buf;
+                }
+                Err(err) => return Poll::Ready(Some(Err(io_error(Operation::Read, err)))),
+            }
+        }
+    }
+}
+
+fn read(fd: RawFd, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match unsafe { syscall!(Sysno::read, fd, buf.as_mut_ptr(), buf.len()) } {
+            Err(Errno::EINTR) => (),
+            Err(err) => return Err(io::Error::from_raw_os_error(err.into_raw())),
+            Ok(len) => return Ok(len),
+        }
+    }
+}
+
+fn io_error(operation: Operation, err: io::Error) -> Error {
+    Error::new(
+        operation,
+        Errno::new(err.raw_os_error().unwrap_or(Errno::EIO.into_raw())),
+    )
+}
+
+/// Returns `dir`, followed by `/` and `name` if not empty.
+fn join(dir: &CStr, name: &[u8]) -> CString {
+    let dir = dir.to_bytes();
+    let name = name.strip_suffix(b"/").unwrap_or(name);
+    let path = if name.is_empty() {
+        dir.to_vec()
+    } else {
+        [dir, b"/", name].concat()
+    };
+    CString::new(path).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imp::tests::open_pty;
+
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn async_lookups() {
+        let err = by_device(Dev::from_split(1, 3)).await.unwrap_err();
+        assert_eq!(err.errno(), Errno::ENOTTY);
+
+        let (_master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+        let tty = by_device(rdev).await.unwrap();
+        assert_eq!(tty.device(), rdev);
+
+        let ttys = by_devices_in([rdev, Dev::from_split(1, 3)], vec![c"/dev/pts".into()])
+            .await
+            .unwrap();
+        assert_eq!(ttys[0].as_ref().unwrap().path(), tty.path());
+        assert_eq!(ttys[1].as_ref().unwrap_err().errno(), Errno::ENOTTY);
+
+        let pid = std::process::id();
+        assert_eq!(
+            for_process(pid)
+                .await
+                .ok()
+                .flatten()
+                .map(|tty| tty.device()),
+            TtyInfo::for_process(pid)
+                .ok()
+                .flatten()
+                .map(|tty| tty.device())
+        );
+    }
+
+    #[tokio::test]
+    async fn pty_events() {
+        let mut events = TtyEvents::new().unwrap();
+        let (master, rdev) = match open_pty() {
+            Some(pty) => pty,
+            None => return,
+        };
+
+        // Other tests create ptys concurrently.
+        let added = loop {
+            let event = ::tokio::time::timeout(Duration::from_secs(5), events.next_event());
+            match event.await.unwrap().unwrap() {
+                TtyEvent::Added(tty) if tty.device() == rdev => break tty,
+                _ => (),
+            }
+        };
+        assert!(added.name().to_bytes().starts_with(b"pts/"));
+
+        drop(master);
+        loop {
+            let event = ::tokio::time::timeout(Duration::from_secs(5), events.next_event());
+            match event.await.unwrap().unwrap() {
+                TtyEvent::Removed { device, path } if device == rdev => {
+                    assert_eq!(path.as_c_str(), added.path());
+                    break;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_events() {
+        fn event(wd: i32, mask: u32, name: &[u8]) -> Vec<u8> {
+            let len = (name.len() + 1).next_multiple_of(4);
+            let header = inotify_event {
+                wd,
+                mask,
+                cookie: 0,
+                len: len as u32,
+                name: Default::default(),
+            };
+            let mut buf = unsafe {
+                std::slice::from_raw_parts(
+                    (&header as *const inotify_event).cast::<u8>(),
+                    mem::size_of::<inotify_event>(),
+                )
+            }
+            .to_vec();
+            buf.extend_from_slice(name);
+            buf.resize(buf.len() + len - name.len(), 0);
+            buf
+        }
+
+        let rt = ::tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+        let _guard = rt.enter();
+        let mut events = TtyEvents::new().unwrap();
+        let wd = *events.watches.iter().find(|(_, p)| p.is_empty()).unwrap().0;
+        events.pending.clear();
+        events
+            .known
+            .insert(b"ttyGONE".to_vec(), Dev::from_split(4, 99));
+
+        let mut buf = event(wd, IN_DELETE, b"ttyGONE");
+        buf.extend(event(wd, IN_DELETE, b"null"));
+        buf.extend(event(wd, IN_CREATE, b"null"));
+        buf.extend(event(wd + 100, IN_CREATE, b"tty1"));
+        events.handle(&buf);
+
+        assert_eq!(events.pending.len(), 1);
+        match events.pending.pop_front().unwrap().unwrap() {
+            TtyEvent::Removed { device, path } => {
+                assert_eq!(device, Dev::from_split(4, 99));
+                assert_eq!(path.as_c_str(), c"/dev/ttyGONE");
+            }
+            event => panic!("unexpected {event:?}"),
+        }
+        assert!(!events.known.contains_key(&b"ttyGONE"[..]));
+    }
+}
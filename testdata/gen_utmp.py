@@ -0,0 +1,43 @@
+#!/usr/bin/env python3
+"""Generates the little-endian utmp fixtures read by src/linux/utmp.rs.
+
+utmp32 uses glibc's layout with a 32-bit ut_session and ut_tv (384 bytes
+records), utmp64 the 64-bit one where both are longs (400 bytes records).
+
+    python3 testdata/gen_utmp.py
+"""
+
+import os
+import struct
+
+RECORDS = [
+    # type, pid, line, id, user, host, termination, exit, session, sec, usec, addr
+    (2, 0, b"~", b"~~", b"reboot", b"6.1.0-test", 0, 0, 0, 1700000000, 0, b""),
+    (6, 812, b"tty1", b"tty1", b"LOGIN", b"", 0, 0, 812, 1700000010, 5, b""),
+    (7, 4242, b"pts/0", b"ts/0", b"alice", b"192.168.1.2", 0, 0, 0, 1700000100, 123456,
+     bytes([192, 168, 1, 2])),
+    (8, 4300, b"pts/1", b"ts/1", b"", b"", 0, 1, 0, 1700000200, 0, b""),
+    (7, 5000, b"tty2", b"tty2", b"a-user-name-exactly-32-bytes-lon", b"", 0, 0, 5000,
+     1700000300, 0, b""),
+]
+
+
+def record(time64, kind, pid, line, id_, user, host, term, exit_, session, sec, usec, addr):
+    head = struct.pack("<hxxi32s4s32s256shh", kind, pid, line, id_, user, host, term, exit_)
+    if time64:
+        tail = struct.pack("<qqq16s", session, sec, usec, addr)
+    else:
+        tail = struct.pack("<iii16s", session, sec, usec, addr)
+    return (head + tail).ljust(400 if time64 else 384, b"\0")
+
+
+def main():
+    here = os.path.dirname(os.path.abspath(__file__))
+    for name, time64 in [("utmp32", False), ("utmp64", True)]:
+        with open(os.path.join(here, name), "wb") as f:
+            for fields in RECORDS:
+                f.write(record(time64, *fields))
+
+
+if __name__ == "__main__":
+    main()